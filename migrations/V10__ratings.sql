@@ -0,0 +1,9 @@
+ALTER TABLE seen_wallpapers ADD COLUMN rating INTEGER;
+ALTER TABLE seen_wallpapers ADD COLUMN shows INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE seen_wallpapers ADD COLUMN skips INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE history ADD COLUMN skipped BOOLEAN NOT NULL DEFAULT 0;
+
+UPDATE seen_wallpapers SET shows = (
+    SELECT COUNT(*) FROM history WHERE history.wallpaper_id = seen_wallpapers.id
+);
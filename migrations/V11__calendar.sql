@@ -0,0 +1 @@
+ALTER TABLE meta ADD COLUMN source_calendar TEXT;
@@ -0,0 +1,3 @@
+ALTER TABLE seen_wallpapers ADD COLUMN hash TEXT;
+
+CREATE INDEX seen_wallpapers_hash ON seen_wallpapers (hash);
@@ -0,0 +1 @@
+ALTER TABLE seen_wallpapers ADD COLUMN in_source BOOLEAN NOT NULL DEFAULT 1;
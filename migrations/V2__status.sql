@@ -0,0 +1 @@
+ALTER TABLE meta ADD COLUMN last_error TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE meta ADD COLUMN paused BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE meta ADD COLUMN paused_until datetime;
@@ -0,0 +1,2 @@
+ALTER TABLE seen_wallpapers ADD COLUMN favorite BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE seen_wallpapers ADD COLUMN weight REAL NOT NULL DEFAULT 1.0;
@@ -0,0 +1 @@
+ALTER TABLE seen_wallpapers ADD COLUMN banned BOOLEAN NOT NULL DEFAULT 0;
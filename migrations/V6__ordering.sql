@@ -0,0 +1,3 @@
+ALTER TABLE seen_wallpapers ADD COLUMN modified INTEGER;
+ALTER TABLE seen_wallpapers ADD COLUMN committed INTEGER;
+ALTER TABLE meta ADD COLUMN cycle_seed INTEGER;
@@ -0,0 +1,5 @@
+CREATE TABLE history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallpaper_id TEXT NOT NULL,
+    shown_at datetime NOT NULL
+);
@@ -0,0 +1,6 @@
+CREATE TABLE tags (
+    wallpaper_id TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    source TEXT NOT NULL,
+    PRIMARY KEY (wallpaper_id, tag, source)
+);
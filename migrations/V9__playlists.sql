@@ -0,0 +1,8 @@
+ALTER TABLE meta ADD COLUMN active_playlist TEXT;
+ALTER TABLE meta ADD COLUMN source_playlists TEXT;
+
+CREATE TABLE playlist_seen (
+    playlist TEXT NOT NULL,
+    wallpaper_id TEXT NOT NULL,
+    PRIMARY KEY (playlist, wallpaper_id)
+);
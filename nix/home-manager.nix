@@ -21,6 +21,15 @@ in {
         default = "local";
       };
 
+      backend = let
+        backendType = types.enum ["auto" "swww" "swaybg" "hyprpaper" "feh" "xwallpaper" "gnome" "plasma" "command"];
+      in
+        lib.mkOption {
+          type = types.either backendType (types.listOf backendType);
+          default = "auto";
+          description = "backend used to set the wallpaper, or a list of backends to try in order";
+        };
+
       update_interval = lib.mkOption {
         type = types.int;
         default = 1440;
@@ -73,6 +82,7 @@ in {
         log_level = cfg.settings.log_level;
         content_manager_type = cfg.settings.content_manager_type;
         update_interval = cfg.settings.update_interval;
+        backend = cfg.settings.backend;
 
         local = {
           path = cfg.settings.local.path;
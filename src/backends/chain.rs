@@ -0,0 +1,124 @@
+use std::cell::Cell;
+
+use crate::wallpaper::Wallpaper;
+
+use super::{Backend, BackendTypes, WallpaperBackend, WallpaperBackendError};
+
+/// Tries each configured backend in order until one of them sets the wallpaper
+///
+/// The backend that last succeeded is tried first next time, so a missing
+/// primary backend is only paid for once
+pub struct BackendChain {
+    backends: Vec<(BackendTypes, Backend)>,
+    active: Cell<usize>,
+}
+
+impl BackendChain {
+    pub fn new(backends: Vec<(BackendTypes, Backend)>) -> BackendChain {
+        BackendChain {
+            backends,
+            active: Cell::new(0),
+        }
+    }
+
+    // the active backend first, followed by the rest in configured order
+    fn order(&self) -> impl Iterator<Item = usize> + '_ {
+        let active = self.active.get();
+        std::iter::once(active).chain((0..self.backends.len()).filter(move |idx| *idx != active))
+    }
+}
+
+impl WallpaperBackend for BackendChain {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let mut last_error = WallpaperBackendError::NotReady;
+
+        for idx in self.order() {
+            let (backend_type, backend) = &self.backends[idx];
+            match backend.set_wallpaper(wallpaper) {
+                Ok(()) => {
+                    if idx != self.active.get() {
+                        tracing::info!("{} backend succeeded, using it from now on", backend_type);
+                        self.active.set(idx);
+                    }
+                    return Ok(());
+                }
+                // no other backend will be able to read the file either
+                Err(err @ WallpaperBackendError::FileUnreadable(_)) => return Err(err),
+                Err(err) => {
+                    tracing::warn!("{} backend failed to set wallpaper: {}", backend_type, err);
+                    last_error = err;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn is_ready(&self) -> bool {
+        self.order().any(|idx| self.backends[idx].1.is_ready())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CONFIG;
+    use crate::backends::command::CommandBackend;
+    use crate::config::{CommandBackendConfig, Config};
+    use crate::content_managers::ContentManagerTypes;
+
+    use super::*;
+
+    fn command(set: &str, ready: &str) -> Backend {
+        Backend::Command(CommandBackend::from_config(&CommandBackendConfig {
+            set: set.to_string(),
+            ready: Some(ready.to_string()),
+            outputs: vec![],
+        }))
+    }
+
+    #[test]
+    fn test_falls_back_and_remembers_backend() {
+        let chain = BackendChain::new(vec![
+            (BackendTypes::Swww, command("false", "false")),
+            (BackendTypes::Command, command("true", "true")),
+        ]);
+        let wallpaper = Wallpaper::new_test_file("chain.png");
+
+        assert!(chain.is_ready());
+        assert_eq!(chain.active.get(), 0);
+        assert!(chain.set_wallpaper(&wallpaper).is_ok());
+        assert_eq!(chain.active.get(), 1);
+        assert_eq!(chain.order().collect::<Vec<_>>(), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_fails_when_every_backend_fails() {
+        let chain = BackendChain::new(vec![
+            (BackendTypes::Swww, command("false", "false")),
+            (BackendTypes::Command, command("false", "false")),
+        ]);
+        let wallpaper = Wallpaper::new_test_file("chain.png");
+
+        assert!(!chain.is_ready());
+        assert!(matches!(
+            chain.set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::Rejected(_))
+        ));
+        assert_eq!(chain.active.get(), 0);
+    }
+
+    #[test]
+    fn test_unreadable_file_stops_chain() {
+        CONFIG.get_or_init(Config::create_config);
+        let chain = BackendChain::new(vec![
+            (BackendTypes::Swww, command("false", "false")),
+            (BackendTypes::Command, command("true", "true")),
+        ]);
+        let wallpaper = Wallpaper::new("missing.png".to_string(), ContentManagerTypes::Local);
+
+        let err = chain.set_wallpaper(&wallpaper).unwrap_err();
+        assert!(matches!(err, WallpaperBackendError::FileUnreadable(_)));
+        assert!(!err.is_retryable());
+        assert_eq!(chain.active.get(), 0);
+    }
+}
@@ -0,0 +1,237 @@
+use std::process::Command;
+
+use crate::config::CommandBackendConfig;
+use crate::get_config;
+use crate::log::Log;
+use crate::log_debug;
+use crate::log_error;
+use crate::wallpaper::Wallpaper;
+
+use super::{WallpaperBackend, WallpaperBackendError, run_command, wallpaper_path};
+
+/// Runs user supplied commands, e.g. `mybg --file {path} --output {output}`
+///
+/// Templates are split into arguments like a shell would, with single and
+/// double quotes and backslash escapes, before placeholders are substituted,
+/// so a path containing spaces is still passed as a single argument
+pub struct CommandBackend {
+    set: String,
+    ready: Option<String>,
+    outputs: Vec<String>,
+}
+
+impl CommandBackend {
+    pub fn new() -> CommandBackend {
+        tracing::info!("using command backend");
+        CommandBackend::from_config(&get_config().file_config.command)
+    }
+
+    pub(crate) fn from_config(config: &CommandBackendConfig) -> CommandBackend {
+        CommandBackend {
+            set: config.set.clone(),
+            ready: config
+                .ready
+                .clone()
+                .filter(|ready| !ready.trim().is_empty()),
+            outputs: config.outputs.clone(),
+        }
+    }
+
+    fn render(
+        template: &str,
+        placeholders: &[(&str, &str)],
+    ) -> Result<Vec<String>, WallpaperBackendError> {
+        Ok(split_arguments(template)?
+            .into_iter()
+            .map(|part| {
+                placeholders.iter().fold(part, |part, (name, value)| {
+                    part.replace(&format!("{{{}}}", name), value)
+                })
+            })
+            .collect())
+    }
+
+    // failures are left to the caller to log, a failing ready command is expected
+    fn run(&self, args: &[String]) -> Result<(), WallpaperBackendError> {
+        let (program, args) = args.split_first().ok_or_else(|| {
+            WallpaperBackendError::UnsupportedOption("command template is empty".to_string())
+        })?;
+        log_debug!(&self, "running {} {}", program, args.join(" "));
+
+        run_command(Command::new(program).args(args))?;
+        Ok(())
+    }
+}
+
+// splits like a shell: whitespace separates arguments except in quotes, a
+// backslash escapes the next character outside single quotes
+fn split_arguments(template: &str) -> Result<Vec<String>, WallpaperBackendError> {
+    let mut arguments = vec![];
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = template.chars();
+
+    while let Some(char) = chars.next() {
+        match (quote, char) {
+            (Some(open), char) if char == open => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                let escaped = chars.next().ok_or_else(|| {
+                    WallpaperBackendError::UnsupportedOption(format!(
+                        "command ends in a backslash: {template}"
+                    ))
+                })?;
+                current.get_or_insert_default().push(escaped);
+            }
+            (Some(_), char) => current.get_or_insert_default().push(char),
+            (None, '\'' | '"') => {
+                quote = Some(char);
+                current.get_or_insert_default();
+            }
+            (None, char) if char.is_whitespace() => arguments.extend(current.take()),
+            (None, char) => current.get_or_insert_default().push(char),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(WallpaperBackendError::UnsupportedOption(format!(
+            "command has an unclosed quote: {template}"
+        )));
+    }
+    arguments.extend(current);
+    Ok(arguments)
+}
+
+impl Log for CommandBackend {
+    fn log_prefix(&self) -> String {
+        "command".to_string()
+    }
+}
+
+impl WallpaperBackend for CommandBackend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let wallpaper_path = &wallpaper_path(wallpaper)?;
+        let source = wallpaper.type_id.to_string();
+        log_debug!(&self, "setting wallpaper {}", wallpaper_path);
+
+        // without any outputs configured the command runs once with an empty {output}
+        let outputs = if self.outputs.is_empty() {
+            vec![String::new()]
+        } else {
+            self.outputs.clone()
+        };
+
+        for output in &outputs {
+            let args = Self::render(
+                &self.set,
+                &[
+                    ("path", wallpaper_path),
+                    ("id", &wallpaper.id),
+                    ("source", &source),
+                    ("output", output),
+                ],
+            )?;
+            self.run(&args)
+                .inspect_err(|err| log_error!(&self, "{}", err))?;
+        }
+
+        log_debug!(&self, "set wallpaper successfully");
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        match &self.ready {
+            // the main loop logs when readiness changes, not on every check
+            Some(ready) => Self::render(ready, &[])
+                .and_then(|args| self.run(&args))
+                .is_ok(),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(set: &str, ready: Option<&str>, outputs: &[&str]) -> CommandBackend {
+        CommandBackend::from_config(&CommandBackendConfig {
+            set: set.to_string(),
+            ready: ready.map(str::to_string),
+            outputs: outputs.iter().map(|output| output.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_render_substitutes_placeholders() {
+        let args = CommandBackend::render(
+            concat!(
+                "mybg --file {path} --output={output} --name {source}:{id} ",
+                r#"--title 'my {id}' "a \"b\"" c\ d ''"#,
+            ),
+            &[
+                ("path", "/wallpapers/with space.png"),
+                ("id", "with space.png"),
+                ("source", "local"),
+                ("output", "DP-1"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            args,
+            vec![
+                "mybg",
+                "--file",
+                "/wallpapers/with space.png",
+                "--output=DP-1",
+                "--name",
+                "local:with space.png",
+                "--title",
+                "my with space.png",
+                "a \"b\"",
+                "c d",
+                "",
+            ]
+        );
+
+        assert!(matches!(
+            CommandBackend::render("mybg 'unclosed", &[]),
+            Err(WallpaperBackendError::UnsupportedOption(_))
+        ));
+        assert!(matches!(
+            CommandBackend::render("mybg \\", &[]),
+            Err(WallpaperBackendError::UnsupportedOption(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_wallpaper_reports_command_status() {
+        let wallpaper = Wallpaper::new_test_file("command.png");
+
+        assert!(
+            backend("true {path}", None, &[])
+                .set_wallpaper(&wallpaper)
+                .is_ok()
+        );
+        assert!(matches!(
+            backend("false {path}", None, &["DP-1", "DP-2"]).set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::Rejected(_))
+        ));
+        assert!(matches!(
+            backend("mirai-missing-setter {path}", None, &[]).set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::BinaryMissing(_))
+        ));
+        assert!(matches!(
+            backend("", None, &[]).set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::UnsupportedOption(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_ready_uses_ready_command() {
+        assert!(backend("true", None, &[]).is_ready());
+        assert!(backend("true", Some(""), &[]).is_ready());
+        assert!(backend("true", Some("true"), &[]).is_ready());
+        assert!(!backend("true", Some("false"), &[]).is_ready());
+    }
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::process::Command;
+
+use which::which;
+
+use crate::config::FehMode;
+use crate::get_config;
+use crate::log::Log;
+use crate::log_debug;
+use crate::log_error;
+use crate::wallpaper::Wallpaper;
+
+use super::{WallpaperBackend, WallpaperBackendError, run_command, wallpaper_path};
+
+pub struct FehBackend {
+    bin: String,
+    mode: FehMode,
+}
+
+impl FehBackend {
+    pub fn new() -> FehBackend {
+        tracing::info!("using feh backend");
+        FehBackend {
+            bin: "feh".to_string(),
+            mode: get_config().file_config.feh.mode,
+        }
+    }
+}
+
+impl Log for FehBackend {
+    fn log_prefix(&self) -> String {
+        "feh".to_string()
+    }
+}
+
+impl WallpaperBackend for FehBackend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let wallpaper_path = wallpaper_path(wallpaper)?;
+        log_debug!(&self, "setting wallpaper {}", wallpaper_path);
+        run_command(
+            Command::new(&self.bin)
+                // mirai restores the wallpaper itself, no need for feh to write ~/.fehbg
+                .args(["--no-fehbg", self.mode.as_arg(), &wallpaper_path]),
+        )
+        .inspect_err(|err| log_error!(&self, "failed to set wallpaper: {}", err))?;
+
+        log_debug!(&self, "set wallpaper successfully");
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        env::var_os("DISPLAY").is_some() && which(&self.bin).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::backends::test_support::stub_binary;
+
+    use super::*;
+
+    #[test]
+    fn test_sets_wallpaper_without_fehbg() {
+        let (bin, log) = stub_binary("feh-set", "feh", "echo \"$@\" >> {log}");
+        let backend = FehBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: FehMode::Max,
+        };
+        let wallpaper = Wallpaper::new_test_file("feh.png");
+
+        backend.set_wallpaper(&wallpaper).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(log).unwrap().trim(),
+            format!(
+                "--no-fehbg --bg-max {}",
+                wallpaper_path(&wallpaper).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_failed_or_missing_feh_is_error() {
+        let (bin, _) = stub_binary(
+            "feh-fail",
+            "feh",
+            "echo 'feh: cannot open display' >&2\nexit 1",
+        );
+        let backend = FehBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: FehMode::Fill,
+        };
+        let wallpaper = Wallpaper::new_test_file("feh.png");
+        assert!(matches!(
+            backend.set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::Rejected(err)) if err.contains("cannot open display")
+        ));
+
+        let missing = FehBackend {
+            bin: "mirai-missing-feh".to_string(),
+            mode: FehMode::Fill,
+        };
+        assert!(matches!(
+            missing.set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::BinaryMissing(_))
+        ));
+        assert!(!missing.is_ready());
+    }
+}
@@ -0,0 +1,121 @@
+use std::process::Command;
+
+use crate::config::GnomeMode;
+use crate::get_config;
+use crate::log::Log;
+use crate::log_debug;
+use crate::log_error;
+use crate::wallpaper::Wallpaper;
+
+use super::{WallpaperBackend, WallpaperBackendError, file_uri, run_command, wallpaper_path};
+
+const SCHEMA: &str = "org.gnome.desktop.background";
+
+pub struct GnomeBackend {
+    bin: String,
+    mode: GnomeMode,
+}
+
+impl GnomeBackend {
+    pub fn new() -> GnomeBackend {
+        tracing::info!("using gnome backend");
+        GnomeBackend {
+            bin: "gsettings".to_string(),
+            mode: get_config().file_config.gnome.mode,
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), WallpaperBackendError> {
+        run_command(Command::new(&self.bin).args(["set", SCHEMA, key, value]))
+            .inspect_err(|err| log_error!(&self, "failed to set {}: {}", key, err))?;
+        Ok(())
+    }
+}
+
+impl Log for GnomeBackend {
+    fn log_prefix(&self) -> String {
+        "gnome".to_string()
+    }
+}
+
+impl WallpaperBackend for GnomeBackend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let wallpaper_path = wallpaper_path(wallpaper)?;
+        log_debug!(&self, "setting wallpaper {}", wallpaper_path);
+
+        let uri = file_uri(&wallpaper_path);
+        self.set("picture-options", self.mode.as_arg())?;
+        // gnome 42+ picks the key matching the current light/dark style
+        self.set("picture-uri", &uri)?;
+        self.set("picture-uri-dark", &uri)?;
+
+        log_debug!(&self, "set wallpaper successfully");
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        let result = Command::new(&self.bin)
+            .args(["get", SCHEMA, "picture-uri"])
+            .output();
+        match result {
+            Ok(cmd) => cmd.status.success(),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::backends::test_support::stub_binary;
+
+    use super::*;
+
+    fn stub_gsettings(name: &str, exit_code: i32) -> (PathBuf, PathBuf) {
+        stub_binary(
+            name,
+            "gsettings",
+            &format!("echo \"$@\" >> {{log}}\nexit {}", exit_code),
+        )
+    }
+
+    #[test]
+    fn test_sets_light_and_dark_uri() {
+        let (bin, log) = stub_gsettings("gnome-set", 0);
+        let backend = GnomeBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: GnomeMode::Zoom,
+        };
+        let wallpaper = Wallpaper::new_test_file("gnome #1 100%.png");
+        let uri = file_uri(&wallpaper_path(&wallpaper).unwrap());
+        assert!(uri.ends_with("/gnome%20%231%20100%25.png"));
+
+        backend.set_wallpaper(&wallpaper).unwrap();
+
+        let calls = fs::read_to_string(log).unwrap();
+        let calls = calls.lines().collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            vec![
+                format!("set {} picture-options zoom", SCHEMA),
+                format!("set {} picture-uri {}", SCHEMA, uri),
+                format!("set {} picture-uri-dark {}", SCHEMA, uri),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_gsettings_is_error() {
+        let (bin, _) = stub_gsettings("gnome-fail", 1);
+        let backend = GnomeBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: GnomeMode::Zoom,
+        };
+        let wallpaper = Wallpaper::new_test_file("gnome.png");
+
+        assert!(backend.set_wallpaper(&wallpaper).is_err());
+        assert!(!backend.is_ready());
+    }
+}
@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::env;
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use crate::get_config;
+use crate::log;
+use crate::log::Log;
+use crate::log_debug;
+use crate::log_error;
+use crate::wallpaper::Wallpaper;
+
+use super::{WallpaperBackend, WallpaperBackendError, wallpaper_path};
+
+const SOCKET_NAME: &str = ".hyprpaper.sock";
+
+/// Talks to hyprpaper over its IPC socket, the same one `hyprctl hyprpaper` uses
+pub struct HyprpaperBackend {
+    monitors: Vec<String>,
+    // found through the hyprland environment variables when not set
+    socket: Option<PathBuf>,
+    // hyprpaper keeps every preloaded image in memory until it is unloaded,
+    // including images that failed to show, which the next change unloads
+    loaded: RefCell<Vec<String>>,
+}
+
+impl HyprpaperBackend {
+    pub fn new() -> HyprpaperBackend {
+        tracing::info!("using hyprpaper backend");
+        HyprpaperBackend {
+            monitors: get_config().file_config.hyprpaper.monitors.clone(),
+            socket: None,
+            loaded: RefCell::new(vec![]),
+        }
+    }
+
+    fn socket_path() -> Option<PathBuf> {
+        let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+
+        // hyprland moved its sockets from /tmp to the runtime dir in 0.40
+        let runtime_dir = env::var("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("hypr"))
+            .into_iter()
+            .chain([PathBuf::from("/tmp/hypr")]);
+
+        runtime_dir
+            .map(|dir| dir.join(&signature).join(SOCKET_NAME))
+            .find(|path| path.exists())
+    }
+
+    fn request(&self, request: &str) -> Result<String, WallpaperBackendError> {
+        let socket_path = self
+            .socket
+            .clone()
+            .or_else(Self::socket_path)
+            .ok_or(WallpaperBackendError::NotReady)?;
+        let mut stream =
+            UnixStream::connect(&socket_path).map_err(|_| WallpaperBackendError::NotReady)?;
+
+        log_debug!(&self, "sending request: {}", request);
+        stream
+            .write_all(request.as_bytes())
+            .and_then(|_| stream.shutdown(Shutdown::Write))
+            .map_err(|err| WallpaperBackendError::Rejected(err.to_string()))?;
+
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(|err| WallpaperBackendError::Rejected(err.to_string()))?;
+
+        Ok(response.trim().to_string())
+    }
+
+    fn command(&self, request: &str) -> Result<(), WallpaperBackendError> {
+        let response = self.request(request)?;
+        if response == "ok" {
+            Ok(())
+        } else {
+            log_error!(&self, "'{}' failed: {}", request, response);
+            Err(WallpaperBackendError::Rejected(response))
+        }
+    }
+}
+
+impl Log for HyprpaperBackend {
+    fn log_prefix(&self) -> String {
+        "hyprpaper".to_string()
+    }
+}
+
+impl WallpaperBackend for HyprpaperBackend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let wallpaper_path = &wallpaper_path(wallpaper)?;
+        log_debug!(&self, "setting wallpaper {}", wallpaper_path);
+
+        self.command(&format!("preload {}", wallpaper_path))?;
+        let mut loaded = self.loaded.borrow_mut();
+        if !loaded.contains(wallpaper_path) {
+            loaded.push(wallpaper_path.to_string());
+        }
+
+        // an empty monitor name applies the wallpaper to every monitor
+        if self.monitors.is_empty() {
+            self.command(&format!("wallpaper ,{}", wallpaper_path))?;
+        }
+        for monitor in &self.monitors {
+            self.command(&format!("wallpaper {},{}", monitor, wallpaper_path))?;
+        }
+
+        for previous in loaded.extract_if(.., |path| path != wallpaper_path) {
+            log!(&self, "unloading previous wallpaper {}", previous);
+            // the new wallpaper is already showing, so failing to free the old one is not fatal
+            let _ = self.command(&format!("unload {}", previous));
+        }
+
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        self.request("listloaded").is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+
+    // stands in for hyprpaper, recording requests and rejecting those starting with `reject`
+    fn stub_socket(name: &str, reject: &'static str) -> (PathBuf, Arc<Mutex<Vec<String>>>) {
+        let dir = std::env::temp_dir().join(format!("mirai-test-{}", name));
+        fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join(SOCKET_NAME);
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().map_while(Result::ok) {
+                let mut request = String::new();
+                stream.read_to_string(&mut request).unwrap();
+                let response = if request.starts_with(reject) {
+                    "invalid monitor"
+                } else {
+                    "ok"
+                };
+                recorded.lock().unwrap().push(request);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        (socket, requests)
+    }
+
+    fn backend(socket: PathBuf, monitors: &[&str]) -> HyprpaperBackend {
+        HyprpaperBackend {
+            monitors: monitors.iter().map(|monitor| monitor.to_string()).collect(),
+            socket: Some(socket),
+            loaded: RefCell::new(vec![]),
+        }
+    }
+
+    fn path(wallpaper: &Wallpaper) -> String {
+        wallpaper
+            .get_wallpaper_path()
+            .unwrap()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn test_preloads_shows_and_unloads_previous() {
+        let (socket, requests) = stub_socket("hyprpaper-set", "none");
+        let backend = backend(socket, &["DP-1", "HDMI-A-1"]);
+        let first = Wallpaper::new_test_file("hyprpaper-1.png");
+        let second = Wallpaper::new_test_file("hyprpaper-2.png");
+
+        assert!(backend.is_ready());
+        backend.set_wallpaper(&first).unwrap();
+        backend.set_wallpaper(&second).unwrap();
+        // setting the same wallpaper again doesn't unload it
+        backend.set_wallpaper(&second).unwrap();
+
+        let (first, second) = (path(&first), path(&second));
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                "listloaded".to_string(),
+                format!("preload {first}"),
+                format!("wallpaper DP-1,{first}"),
+                format!("wallpaper HDMI-A-1,{first}"),
+                format!("preload {second}"),
+                format!("wallpaper DP-1,{second}"),
+                format!("wallpaper HDMI-A-1,{second}"),
+                format!("unload {first}"),
+                format!("preload {second}"),
+                format!("wallpaper DP-1,{second}"),
+                format!("wallpaper HDMI-A-1,{second}"),
+            ]
+        );
+        assert_eq!(*backend.loaded.borrow(), vec![second]);
+    }
+
+    #[test]
+    fn test_failed_wallpaper_is_unloaded_by_next_change() {
+        let (socket, requests) = stub_socket("hyprpaper-fail", "wallpaper ,");
+        let failing = backend(socket.clone(), &[]);
+        let wallpaper = Wallpaper::new_test_file("hyprpaper-failed.png");
+
+        assert!(matches!(
+            failing.set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::Rejected(_))
+        ));
+        assert_eq!(*failing.loaded.borrow(), vec![path(&wallpaper)]);
+
+        // the same backend with a monitor that works
+        let working = HyprpaperBackend {
+            monitors: vec!["DP-1".to_string()],
+            ..failing
+        };
+        let next = Wallpaper::new_test_file("hyprpaper-next.png");
+        working.set_wallpaper(&next).unwrap();
+
+        assert_eq!(
+            requests.lock().unwrap().last(),
+            Some(&format!("unload {}", path(&wallpaper)))
+        );
+        assert_eq!(*working.loaded.borrow(), vec![path(&next)]);
+        assert!(
+            backend(socket.with_extension("missing"), &[])
+                .set_wallpaper(&next)
+                .is_err()
+        );
+    }
+}
@@ -1,19 +1,449 @@
+pub mod chain;
+pub mod command;
+#[cfg(not(target_os = "windows"))]
+pub mod feh;
+#[cfg(not(target_os = "windows"))]
+pub mod gnome;
+#[cfg(not(target_os = "windows"))]
+pub mod hyprpaper;
+#[cfg(not(target_os = "windows"))]
+pub mod plasma;
+#[cfg(test)]
+pub mod recording;
+#[cfg(not(target_os = "windows"))]
+pub mod swaybg;
 #[cfg(not(target_os = "windows"))]
 pub mod swww_cli;
 #[cfg(target_os = "windows")]
 pub mod windows;
+#[cfg(not(target_os = "windows"))]
+pub mod xwallpaper;
 
+use std::env;
+use std::fmt::Display;
+use std::io;
+use std::process::{Command, Output};
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use which::which;
 
 use crate::wallpaper::Wallpaper;
 
 #[derive(Error, Debug)]
 pub enum WallpaperBackendError {
-    #[error("failed to change wallpaper")]
-    ChangeFailure,
+    #[error("backend is not ready")]
+    NotReady,
+    #[error("{0} is not installed")]
+    BinaryMissing(String),
+    #[error("wallpaper {0} could not be read")]
+    FileUnreadable(String),
+    #[error("wallpaper was rejected: {0}")]
+    Rejected(String),
+    #[error("unsupported option: {0}")]
+    UnsupportedOption(String),
+}
+
+impl WallpaperBackendError {
+    /// Whether trying again later with the same wallpaper and backend could succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            WallpaperBackendError::NotReady | WallpaperBackendError::Rejected(_)
+        )
+    }
 }
 
 pub trait WallpaperBackend {
     fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError>;
     fn is_ready(&self) -> bool;
 }
+
+/// Resolves the file backing a wallpaper, for backends that are given a path
+pub fn wallpaper_path(wallpaper: &Wallpaper) -> Result<String, WallpaperBackendError> {
+    wallpaper
+        .get_wallpaper_path()
+        .ok()
+        .and_then(|path| path.to_str().map(str::to_string))
+        .ok_or_else(|| WallpaperBackendError::FileUnreadable(wallpaper.id.clone()))
+}
+
+/// `file://` URI of an absolute path, percent-encoding every byte that isn't
+/// an unreserved character or a `/`, e.g. spaces, `#` and `%`
+#[cfg(not(target_os = "windows"))]
+pub fn file_uri(path: &str) -> String {
+    path.bytes().fold("file://".to_string(), |mut uri, byte| {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+        uri
+    })
+}
+
+/// Runs a command, capturing what it printed if it fails
+pub fn run_command(command: &mut Command) -> Result<Output, WallpaperBackendError> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command.output().map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => WallpaperBackendError::BinaryMissing(program.clone()),
+        _ => WallpaperBackendError::Rejected(format!("failed to run {}: {}", program, err)),
+    })?;
+
+    if output.status.success() {
+        return Ok(output);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // not every setter writes its errors to stderr
+    let captured = if stderr.trim().is_empty() {
+        stdout.trim()
+    } else {
+        stderr.trim()
+    };
+    Err(WallpaperBackendError::Rejected(format!(
+        "{} exited with {}: {}",
+        program, output.status, captured
+    )))
+}
+
+pub enum Backend {
+    #[cfg(not(target_os = "windows"))]
+    Swww(swww_cli::SwwCliBackend),
+    #[cfg(not(target_os = "windows"))]
+    Swaybg(swaybg::SwaybgBackend),
+    #[cfg(not(target_os = "windows"))]
+    Hyprpaper(hyprpaper::HyprpaperBackend),
+    #[cfg(not(target_os = "windows"))]
+    Feh(feh::FehBackend),
+    #[cfg(not(target_os = "windows"))]
+    XWallpaper(xwallpaper::XWallpaperBackend),
+    #[cfg(not(target_os = "windows"))]
+    Gnome(gnome::GnomeBackend),
+    #[cfg(not(target_os = "windows"))]
+    Plasma(plasma::PlasmaBackend),
+    #[cfg(target_os = "windows")]
+    Windows(windows::Windows),
+    Command(command::CommandBackend),
+}
+
+impl Backend {
+    pub fn new(backend_type: BackendTypes) -> Result<Backend, String> {
+        match backend_type {
+            #[cfg(not(target_os = "windows"))]
+            BackendTypes::Swww => Ok(Backend::Swww(swww_cli::SwwCliBackend::new())),
+            #[cfg(not(target_os = "windows"))]
+            BackendTypes::Swaybg => Ok(Backend::Swaybg(swaybg::SwaybgBackend::new())),
+            #[cfg(not(target_os = "windows"))]
+            BackendTypes::Hyprpaper => Ok(Backend::Hyprpaper(hyprpaper::HyprpaperBackend::new())),
+            #[cfg(not(target_os = "windows"))]
+            BackendTypes::Feh => Ok(Backend::Feh(feh::FehBackend::new())),
+            #[cfg(not(target_os = "windows"))]
+            BackendTypes::XWallpaper => {
+                Ok(Backend::XWallpaper(xwallpaper::XWallpaperBackend::new()))
+            }
+            #[cfg(not(target_os = "windows"))]
+            BackendTypes::Gnome => Ok(Backend::Gnome(gnome::GnomeBackend::new())),
+            #[cfg(not(target_os = "windows"))]
+            BackendTypes::Plasma => Ok(Backend::Plasma(plasma::PlasmaBackend::new())),
+            #[cfg(target_os = "windows")]
+            BackendTypes::Windows => Ok(Backend::Windows(windows::Windows::new())),
+            BackendTypes::Command => Ok(Backend::Command(command::CommandBackend::new())),
+            BackendTypes::Auto => Err("auto backend must be resolved before creation".to_string()),
+            unsupported => Err(format!(
+                "{} backend is not supported on this platform",
+                unsupported
+            )),
+        }
+    }
+}
+
+impl WallpaperBackend for Backend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        match self {
+            #[cfg(not(target_os = "windows"))]
+            Backend::Swww(backend) => backend.set_wallpaper(wallpaper),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Swaybg(backend) => backend.set_wallpaper(wallpaper),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Hyprpaper(backend) => backend.set_wallpaper(wallpaper),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Feh(backend) => backend.set_wallpaper(wallpaper),
+            #[cfg(not(target_os = "windows"))]
+            Backend::XWallpaper(backend) => backend.set_wallpaper(wallpaper),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Gnome(backend) => backend.set_wallpaper(wallpaper),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Plasma(backend) => backend.set_wallpaper(wallpaper),
+            #[cfg(target_os = "windows")]
+            Backend::Windows(backend) => backend.set_wallpaper(wallpaper),
+            Backend::Command(backend) => backend.set_wallpaper(wallpaper),
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        match self {
+            #[cfg(not(target_os = "windows"))]
+            Backend::Swww(backend) => backend.is_ready(),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Swaybg(backend) => backend.is_ready(),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Hyprpaper(backend) => backend.is_ready(),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Feh(backend) => backend.is_ready(),
+            #[cfg(not(target_os = "windows"))]
+            Backend::XWallpaper(backend) => backend.is_ready(),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Gnome(backend) => backend.is_ready(),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Plasma(backend) => backend.is_ready(),
+            #[cfg(target_os = "windows")]
+            Backend::Windows(backend) => backend.is_ready(),
+            Backend::Command(backend) => backend.is_ready(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendTypes {
+    #[default]
+    Auto,
+    Swww,
+    Swaybg,
+    Hyprpaper,
+    Feh,
+    XWallpaper,
+    Gnome,
+    Plasma,
+    Windows,
+    Command,
+}
+
+impl Display for BackendTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BackendTypes::Auto => "auto",
+            BackendTypes::Swww => "swww",
+            BackendTypes::Swaybg => "swaybg",
+            BackendTypes::Hyprpaper => "hyprpaper",
+            BackendTypes::Feh => "feh",
+            BackendTypes::XWallpaper => "xwallpaper",
+            BackendTypes::Gnome => "gnome",
+            BackendTypes::Plasma => "plasma",
+            BackendTypes::Windows => "windows",
+            BackendTypes::Command => "command",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A single backend, or an ordered list of backends to fall back through
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BackendSelection {
+    Single(BackendTypes),
+    Chain(Vec<BackendTypes>),
+}
+
+impl Default for BackendSelection {
+    fn default() -> Self {
+        BackendSelection::Single(BackendTypes::default())
+    }
+}
+
+impl BackendSelection {
+    pub fn backends(&self) -> Vec<BackendTypes> {
+        match self {
+            BackendSelection::Single(backend_type) => vec![*backend_type],
+            BackendSelection::Chain(backend_types) => backend_types.clone(),
+        }
+    }
+}
+
+/// Picks a backend for the current session, along with the reason it was picked
+pub fn detect_backend() -> (BackendTypes, String) {
+    detect_backend_with(|key| env::var(key).ok(), |bin| which(bin).is_ok())
+}
+
+fn detect_backend_with(
+    var: impl Fn(&str) -> Option<String>,
+    has_bin: impl Fn(&str) -> bool,
+) -> (BackendTypes, String) {
+    if cfg!(target_os = "windows") {
+        return (BackendTypes::Windows, "running on windows".to_string());
+    }
+
+    let var = |key: &str| var(key).filter(|value| !value.is_empty());
+    // XDG_CURRENT_DESKTOP is a colon separated list, e.g. "ubuntu:GNOME"
+    let desktops = var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_lowercase()
+        .split(':')
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let on_desktop = |name: &str| desktops.iter().any(|desktop| desktop == name);
+
+    if on_desktop("gnome") && has_bin("gsettings") {
+        return (
+            BackendTypes::Gnome,
+            "XDG_CURRENT_DESKTOP is GNOME".to_string(),
+        );
+    }
+    if on_desktop("kde") && has_bin("dbus-send") {
+        return (
+            BackendTypes::Plasma,
+            "XDG_CURRENT_DESKTOP is KDE".to_string(),
+        );
+    }
+
+    if var("WAYLAND_DISPLAY").is_some() {
+        if on_desktop("hyprland") && has_bin("hyprpaper") {
+            return (
+                BackendTypes::Hyprpaper,
+                "running under Hyprland with hyprpaper installed".to_string(),
+            );
+        }
+        if has_bin("swww") || has_bin("awww") {
+            return (
+                BackendTypes::Swww,
+                "WAYLAND_DISPLAY is set and swww is installed".to_string(),
+            );
+        }
+        if has_bin("swaybg") {
+            return (
+                BackendTypes::Swaybg,
+                "WAYLAND_DISPLAY is set and swaybg is installed".to_string(),
+            );
+        }
+    } else if var("DISPLAY").is_some() {
+        if has_bin("feh") {
+            return (
+                BackendTypes::Feh,
+                "DISPLAY is set without WAYLAND_DISPLAY and feh is installed".to_string(),
+            );
+        }
+        if has_bin("xwallpaper") {
+            return (
+                BackendTypes::XWallpaper,
+                "DISPLAY is set without WAYLAND_DISPLAY and xwallpaper is installed".to_string(),
+            );
+        }
+    }
+
+    (
+        BackendTypes::Swww,
+        "no supported session detected, falling back to swww".to_string(),
+    )
+}
+
+/// Helpers shared by the tests of backends that run a binary
+#[cfg(all(test, not(target_os = "windows")))]
+pub mod test_support {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    use crate::CONFIG;
+    use crate::config::Config;
+
+    /// Writes a shell script standing in for `bin`, in a directory of the
+    /// test's own. `{log}` in the script is replaced by a file the script
+    /// can record its calls in, which is returned along with its path
+    pub fn stub_binary(test: &str, bin: &str, script: &str) -> (PathBuf, PathBuf) {
+        CONFIG.get_or_init(Config::create_config);
+
+        let dir = std::env::temp_dir().join(format!("mirai-test-{}", test));
+        fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("calls.log");
+        let _ = fs::remove_file(&log_path);
+        let bin_path = dir.join(bin);
+        fs::write(
+            &bin_path,
+            format!(
+                "#!/bin/sh\n{}\n",
+                script.replace("{log}", &log_path.display().to_string())
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&bin_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        (bin_path, log_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)], bins: &[&str]) -> BackendTypes {
+        let vars = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        let (backend, _) =
+            detect_backend_with(|key| vars.get(key).cloned(), |bin| bins.contains(&bin));
+        backend
+    }
+
+    #[test]
+    fn test_detects_desktop_environments() {
+        let gnome = detect(
+            &[
+                ("XDG_CURRENT_DESKTOP", "ubuntu:GNOME"),
+                ("WAYLAND_DISPLAY", "wayland-0"),
+            ],
+            &["gsettings", "swww"],
+        );
+        assert_eq!(gnome, BackendTypes::Gnome);
+
+        let plasma = detect(
+            &[("XDG_CURRENT_DESKTOP", "KDE"), ("DISPLAY", ":0")],
+            &["dbus-send"],
+        );
+        assert_eq!(plasma, BackendTypes::Plasma);
+    }
+
+    #[test]
+    fn test_detects_wayland_setters() {
+        let wayland = [
+            ("WAYLAND_DISPLAY", "wayland-1"),
+            ("XDG_CURRENT_DESKTOP", "Hyprland"),
+        ];
+        assert_eq!(
+            detect(&wayland, &["hyprpaper", "swww"]),
+            BackendTypes::Hyprpaper
+        );
+        assert_eq!(detect(&wayland, &["swww", "swaybg"]), BackendTypes::Swww);
+        assert_eq!(detect(&wayland, &["swaybg"]), BackendTypes::Swaybg);
+    }
+
+    #[test]
+    fn test_detects_x11_setters() {
+        let x11 = [("DISPLAY", ":0"), ("XDG_CURRENT_DESKTOP", "i3")];
+        assert_eq!(detect(&x11, &["feh", "xwallpaper"]), BackendTypes::Feh);
+        assert_eq!(detect(&x11, &["xwallpaper"]), BackendTypes::XWallpaper);
+        // an empty WAYLAND_DISPLAY does not make this a wayland session
+        let x11 = [("DISPLAY", ":0"), ("WAYLAND_DISPLAY", "")];
+        assert_eq!(detect(&x11, &["feh", "swww"]), BackendTypes::Feh);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_file_uri() {
+        assert_eq!(
+            file_uri("/home/me/my walls/#1 100%.png"),
+            "file:///home/me/my%20walls/%231%20100%25.png"
+        );
+        assert_eq!(file_uri("/tmp/café.jpg"), "file:///tmp/caf%C3%A9.jpg");
+    }
+
+    #[test]
+    fn test_falls_back_to_swww() {
+        assert_eq!(detect(&[], &[]), BackendTypes::Swww);
+    }
+}
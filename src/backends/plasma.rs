@@ -0,0 +1,152 @@
+use std::process::Command;
+
+use crate::config::PlasmaMode;
+use crate::get_config;
+use crate::log::Log;
+use crate::log_debug;
+use crate::log_error;
+use crate::wallpaper::Wallpaper;
+
+use super::{WallpaperBackend, WallpaperBackendError, file_uri, run_command, wallpaper_path};
+
+/// Sets the wallpaper through the plasmashell scripting interface on the session bus
+pub struct PlasmaBackend {
+    bin: String,
+    mode: PlasmaMode,
+}
+
+impl PlasmaBackend {
+    pub fn new() -> PlasmaBackend {
+        tracing::info!("using plasma backend");
+        PlasmaBackend {
+            bin: "dbus-send".to_string(),
+            mode: get_config().file_config.plasma.mode,
+        }
+    }
+
+    fn script(&self, wallpaper_path: &str) -> String {
+        // passed as a JS string literal, which can't be ended early by the
+        // percent-encoded uri
+        let uri = file_uri(wallpaper_path);
+        format!(
+            "desktops().forEach(d => {{ \
+                d.wallpaperPlugin = \"org.kde.image\"; \
+                d.currentConfigGroup = [\"Wallpaper\", \"org.kde.image\", \"General\"]; \
+                d.writeConfig(\"Image\", \"{}\"); \
+                d.writeConfig(\"FillMode\", {}); \
+            }});",
+            uri,
+            self.mode.as_fill_mode()
+        )
+    }
+}
+
+impl Log for PlasmaBackend {
+    fn log_prefix(&self) -> String {
+        "plasma".to_string()
+    }
+}
+
+impl WallpaperBackend for PlasmaBackend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let wallpaper_path = wallpaper_path(wallpaper)?;
+        log_debug!(&self, "setting wallpaper {}", wallpaper_path);
+
+        run_command(
+            Command::new(&self.bin)
+                .args([
+                    "--session",
+                    "--print-reply",
+                    "--type=method_call",
+                    "--dest=org.kde.plasmashell",
+                    "/PlasmaShell",
+                    "org.kde.PlasmaShell.evaluateScript",
+                ])
+                .arg(format!("string:{}", self.script(&wallpaper_path))),
+        )
+        .inspect_err(|err| log_error!(&self, "failed to set wallpaper: {}", err))?;
+
+        log_debug!(&self, "set wallpaper successfully");
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        let result = Command::new(&self.bin)
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.freedesktop.DBus",
+                "/org/freedesktop/DBus",
+                "org.freedesktop.DBus.NameHasOwner",
+                "string:org.kde.plasmashell",
+            ])
+            .output();
+        match result {
+            Ok(cmd) => {
+                cmd.status.success()
+                    && String::from_utf8_lossy(&cmd.stdout).contains("boolean true")
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::backends::test_support::stub_binary;
+
+    use super::*;
+
+    // stands in for the session bus, answering NameHasOwner with `has_owner`
+    fn stub_dbus_send(name: &str, has_owner: bool) -> (PathBuf, PathBuf) {
+        stub_binary(
+            name,
+            "dbus-send",
+            &format!(
+                "for arg in \"$@\"; do echo \"$arg\" >> {{log}}; done\necho \"   boolean {}\"",
+                has_owner
+            ),
+        )
+    }
+
+    #[test]
+    fn test_evaluates_script_on_plasmashell() {
+        let (bin, log) = stub_dbus_send("plasma-set", true);
+        let backend = PlasmaBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: PlasmaMode::Crop,
+        };
+        let wallpaper = Wallpaper::new_test_file("plasma.png");
+        let path = wallpaper.get_wallpaper_path().unwrap();
+
+        backend.set_wallpaper(&wallpaper).unwrap();
+
+        let calls = fs::read_to_string(log).unwrap();
+        let calls = calls.lines().collect::<Vec<_>>();
+        assert!(calls.contains(&"--dest=org.kde.plasmashell"));
+        assert!(calls.contains(&"org.kde.PlasmaShell.evaluateScript"));
+        let script = calls.iter().find(|arg| arg.starts_with("string:")).unwrap();
+        assert!(script.contains(&format!("\"{}\"", file_uri(&path.to_string_lossy()))));
+        assert!(script.contains("\"FillMode\", 2"));
+    }
+
+    #[test]
+    fn test_ready_when_plasmashell_owns_name() {
+        let (bin, _) = stub_dbus_send("plasma-ready", true);
+        let backend = PlasmaBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: PlasmaMode::Crop,
+        };
+        assert!(backend.is_ready());
+
+        let (bin, _) = stub_dbus_send("plasma-not-ready", false);
+        let backend = PlasmaBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: PlasmaMode::Crop,
+        };
+        assert!(!backend.is_ready());
+    }
+}
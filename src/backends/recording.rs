@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::wallpaper::Wallpaper;
+
+use super::{WallpaperBackend, WallpaperBackendError, wallpaper_path};
+
+#[derive(Default)]
+struct RecordingState {
+    calls: Vec<String>,
+    current: Option<String>,
+    failures: VecDeque<WallpaperBackendError>,
+    not_ready: bool,
+}
+
+/// In-memory backend for tests, recording every call it receives
+///
+/// Clones share their state, so a test can keep a handle after giving the
+/// backend to a `WallpapersManager`
+#[derive(Clone, Default)]
+pub struct RecordingBackend {
+    state: Rc<RefCell<RecordingState>>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> RecordingBackend {
+        RecordingBackend::default()
+    }
+
+    /// Fails the next `set_wallpaper` call with `err`, queued failures are used in order
+    pub fn fail_next(&self, err: WallpaperBackendError) {
+        self.state.borrow_mut().failures.push_back(err);
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.state.borrow_mut().not_ready = !ready;
+    }
+
+    /// Resolved paths of every `set_wallpaper` call, including failed ones
+    pub fn calls(&self) -> Vec<String> {
+        self.state.borrow().calls.clone()
+    }
+
+    /// Path of the wallpaper currently "on screen"
+    pub fn current(&self) -> Option<String> {
+        self.state.borrow().current.clone()
+    }
+}
+
+impl WallpaperBackend for RecordingBackend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let path = wallpaper_path(wallpaper)?;
+        let mut state = self.state.borrow_mut();
+        state.calls.push(path.clone());
+
+        if let Some(err) = state.failures.pop_front() {
+            return Err(err);
+        }
+
+        state.current = Some(path);
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        !self.state.borrow().not_ready
+    }
+}
@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::env;
+use std::io;
+use std::process::{Child, Command, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+use which::which;
+
+use crate::config::SwaybgMode;
+use crate::get_config;
+use crate::log::Log;
+use crate::log_debug;
+use crate::log_error;
+use crate::wallpaper::Wallpaper;
+
+use super::{WallpaperBackend, WallpaperBackendError, wallpaper_path};
+
+// how long a new swaybg gets to draw before the previous one is killed
+const HANDOVER_DELAY: Duration = Duration::from_millis(500);
+
+/// swaybg has no IPC, each wallpaper is drawn by its own long running process
+pub struct SwaybgBackend {
+    mode: SwaybgMode,
+    child: RefCell<Option<Child>>,
+}
+
+impl SwaybgBackend {
+    pub fn new() -> SwaybgBackend {
+        tracing::info!("using swaybg backend");
+        SwaybgBackend {
+            mode: get_config().file_config.swaybg.mode,
+            child: RefCell::new(None),
+        }
+    }
+}
+
+impl Log for SwaybgBackend {
+    fn log_prefix(&self) -> String {
+        "swaybg".to_string()
+    }
+}
+
+impl WallpaperBackend for SwaybgBackend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let wallpaper_path = wallpaper_path(wallpaper)?;
+        log_debug!(&self, "setting wallpaper {}", wallpaper_path);
+
+        let mut child = Command::new("swaybg")
+            .args(["-i", &wallpaper_path, "-m", self.mode.as_arg()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound => {
+                    WallpaperBackendError::BinaryMissing("swaybg".to_string())
+                }
+                _ => WallpaperBackendError::Rejected(err.to_string()),
+            })?;
+
+        sleep(HANDOVER_DELAY);
+        if let Ok(Some(status)) = child.try_wait() {
+            log_error!(&self, "swaybg exited early with {}", status);
+            return Err(WallpaperBackendError::Rejected(format!(
+                "swaybg exited early with {}",
+                status
+            )));
+        }
+
+        if let Some(mut previous) = self.child.replace(Some(child)) {
+            let _ = previous.kill();
+            let _ = previous.wait();
+        }
+
+        log_debug!(&self, "set wallpaper successfully");
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        env::var_os("WAYLAND_DISPLAY").is_some() && which("swaybg").is_ok()
+    }
+}
+
+impl Drop for SwaybgBackend {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
@@ -7,7 +7,7 @@ use crate::log_debug;
 use crate::log_error;
 use crate::wallpaper::Wallpaper;
 
-use super::{WallpaperBackend, WallpaperBackendError};
+use super::{WallpaperBackend, WallpaperBackendError, run_command, wallpaper_path};
 
 /// TEMP cli powered backend because I cannot get the socket to connect
 pub struct SwwCliBackend {
@@ -30,25 +30,17 @@ impl Log for SwwCliBackend {
 
 impl WallpaperBackend for SwwCliBackend {
     fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
-        let wallpaper_path = &wallpaper
-            .get_wallpaper_path()
-            .map_err(|_| WallpaperBackendError::ChangeFailure)?;
-        let wallpaper_path = wallpaper_path.to_str().unwrap();
+        let wallpaper_path = wallpaper_path(wallpaper)?;
         log_debug!(&self, "setting wallpaper {}", wallpaper_path);
-        let output = Command::new(self.bin_name)
-            // TODO: setup config option for resize
-            .args(["img", "--resize=fit", wallpaper_path])
-            .output()
-            .map_err(|_| WallpaperBackendError::ChangeFailure)?;
-
-        if output.status.success() {
-            log_debug!(&self, "set wallpaper successfully");
-            Ok(())
-        } else {
-            tracing::error!("[swww-cli] ");
-            log_error!(&self, "failed to set wallpaper");
-            Err(WallpaperBackendError::ChangeFailure)
-        }
+        run_command(
+            Command::new(self.bin_name)
+                // TODO: setup config option for resize
+                .args(["img", "--resize=fit", &wallpaper_path]),
+        )
+        .inspect_err(|err| log_error!(&self, "failed to set wallpaper: {}", err))?;
+
+        log_debug!(&self, "set wallpaper successfully");
+        Ok(())
     }
 
     fn is_ready(&self) -> bool {
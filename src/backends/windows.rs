@@ -10,7 +10,7 @@ use winapi::um::winuser::SystemParametersInfoW;
 use winreg::RegKey;
 use winreg::enums::*;
 
-use super::{WallpaperBackend, WallpaperBackendError};
+use super::{WallpaperBackend, WallpaperBackendError, wallpaper_path};
 
 pub struct Windows;
 
@@ -23,10 +23,7 @@ impl Windows {
 
 impl WallpaperBackend for Windows {
     fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
-        let wallpaper_path = &wallpaper
-            .get_wallpaper_path()
-            .map_err(|_| WallpaperBackendError::ChangeFailure)?;
-        let wallpaper_path = wallpaper_path.to_str().unwrap();
+        let wallpaper_path = &wallpaper_path(wallpaper)?;
         tracing::debug!("[windows] setting wallpaper {}", wallpaper_path);
 
         unsafe {
@@ -43,18 +40,21 @@ impl WallpaperBackend for Windows {
             ) == 1;
 
             if !successful {
-                return Err(WallpaperBackendError::ChangeFailure);
+                return Err(WallpaperBackendError::Rejected(format!(
+                    "SystemParametersInfoW failed: {}",
+                    std::io::Error::last_os_error()
+                )));
             }
 
             // TODO:add config option for mode
             let hkcu = RegKey::predef(HKEY_CURRENT_USER);
             let (desktop, _) = hkcu
                 .create_subkey(r"Control Panel\Desktop")
-                .map_err(|_| WallpaperBackendError::ChangeFailure)?;
+                .map_err(|err| WallpaperBackendError::Rejected(err.to_string()))?;
 
             desktop
                 .set_value("WallpaperStyle", &"6".to_string())
-                .map_err(|_| WallpaperBackendError::ChangeFailure)?;
+                .map_err(|err| WallpaperBackendError::Rejected(err.to_string()))?;
 
             Ok(())
         }
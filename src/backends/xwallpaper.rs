@@ -0,0 +1,100 @@
+use std::env;
+use std::process::Command;
+
+use which::which;
+
+use crate::config::XWallpaperMode;
+use crate::get_config;
+use crate::log::Log;
+use crate::log_debug;
+use crate::log_error;
+use crate::wallpaper::Wallpaper;
+
+use super::{WallpaperBackend, WallpaperBackendError, run_command, wallpaper_path};
+
+pub struct XWallpaperBackend {
+    bin: String,
+    mode: XWallpaperMode,
+}
+
+impl XWallpaperBackend {
+    pub fn new() -> XWallpaperBackend {
+        tracing::info!("using xwallpaper backend");
+        XWallpaperBackend {
+            bin: "xwallpaper".to_string(),
+            mode: get_config().file_config.xwallpaper.mode,
+        }
+    }
+}
+
+impl Log for XWallpaperBackend {
+    fn log_prefix(&self) -> String {
+        "xwallpaper".to_string()
+    }
+}
+
+impl WallpaperBackend for XWallpaperBackend {
+    fn set_wallpaper(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let wallpaper_path = wallpaper_path(wallpaper)?;
+        log_debug!(&self, "setting wallpaper {}", wallpaper_path);
+        run_command(Command::new(&self.bin).args([self.mode.as_arg(), &wallpaper_path]))
+            .inspect_err(|err| log_error!(&self, "failed to set wallpaper: {}", err))?;
+
+        log_debug!(&self, "set wallpaper successfully");
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        env::var_os("DISPLAY").is_some() && which(&self.bin).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::backends::test_support::stub_binary;
+
+    use super::*;
+
+    #[test]
+    fn test_sets_wallpaper_with_mode() {
+        let (bin, log) = stub_binary("xwallpaper-set", "xwallpaper", "echo \"$@\" >> {log}");
+        let backend = XWallpaperBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: XWallpaperMode::Tile,
+        };
+        let wallpaper = Wallpaper::new_test_file("xwallpaper.png");
+
+        backend.set_wallpaper(&wallpaper).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(log).unwrap().trim(),
+            format!("--tile {}", wallpaper_path(&wallpaper).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_failed_or_missing_xwallpaper_is_error() {
+        let (bin, _) = stub_binary("xwallpaper-fail", "xwallpaper", "exit 1");
+        let backend = XWallpaperBackend {
+            bin: bin.to_str().unwrap().to_string(),
+            mode: XWallpaperMode::Zoom,
+        };
+        let wallpaper = Wallpaper::new_test_file("xwallpaper.png");
+        assert!(matches!(
+            backend.set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::Rejected(_))
+        ));
+
+        let missing = XWallpaperBackend {
+            bin: "mirai-missing-xwallpaper".to_string(),
+            mode: XWallpaperMode::Zoom,
+        };
+        assert!(matches!(
+            missing.set_wallpaper(&wallpaper),
+            Err(WallpaperBackendError::BinaryMissing(_))
+        ));
+        assert!(!missing.is_ready());
+    }
+}
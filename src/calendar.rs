@@ -0,0 +1,183 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::get_config;
+use crate::store::Store;
+
+/// Calendar file at the root of a wallpaper source, holding `[[overrides]]`
+/// like the config does
+pub const CALENDAR_FILE: &str = "calendar.toml";
+
+/// Pins a wallpaper, or the wallpapers with a tag, to a day or a range of days
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DateOverride {
+    // "YYYY-MM-DD", or "MM-DD" to repeat every year
+    #[serde(alias = "date")]
+    pub start: String,
+    // last day of the range, inclusive, defaults to the start
+    pub end: Option<String>,
+    pub wallpaper: Option<String>,
+    pub tag: Option<String>,
+    // repeat on the same days every year, ignoring the year of the dates
+    #[serde(default)]
+    pub yearly: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CalendarFile {
+    #[serde(default)]
+    overrides: Vec<DateOverride>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Day {
+    Date(NaiveDate),
+    // month and day
+    Yearly(u32, u32),
+}
+
+impl Day {
+    fn month_day(&self) -> (u32, u32) {
+        match self {
+            Day::Date(date) => (date.month(), date.day()),
+            Day::Yearly(month, day) => (*month, *day),
+        }
+    }
+}
+
+impl DateOverride {
+    /// Whether the override applies on `today`, invalid dates never do
+    pub fn is_active(&self, today: NaiveDate) -> bool {
+        let Some(start) = parse_day(&self.start) else {
+            return false;
+        };
+        let end = match &self.end {
+            Some(end) => match parse_day(end) {
+                Some(end) => end,
+                None => return false,
+            },
+            None => start,
+        };
+
+        match (start, end) {
+            (Day::Date(start), Day::Date(end)) if !self.yearly => start <= today && today <= end,
+            _ => {
+                let (start, end) = (start.month_day(), end.month_day());
+                let today = (today.month(), today.day());
+                if start <= end {
+                    start <= today && today <= end
+                } else {
+                    // wraps around the new year, e.g. 12-24 to 01-01
+                    today >= start || today <= end
+                }
+            }
+        }
+    }
+}
+
+fn parse_day(value: &str) -> Option<Day> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(Day::Date(date));
+    }
+
+    // checked against a leap year so 02-29 is allowed
+    let (month, day) = value.split_once('-')?;
+    let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+    NaiveDate::from_ymd_opt(2000, month, day)?;
+    Some(Day::Yearly(month, day))
+}
+
+pub fn parse(contents: &str) -> Result<Vec<DateOverride>, toml::de::Error> {
+    toml::from_str::<CalendarFile>(contents).map(|calendar| calendar.overrides)
+}
+
+/// The override in effect on `today`, the config's taking precedence over
+/// the source's calendar file and earlier entries over later ones
+pub fn active_override(store: &Store, today: NaiveDate) -> Option<DateOverride> {
+    let source = match store.get_source_calendar().map(|contents| parse(&contents)) {
+        Some(Ok(overrides)) => overrides,
+        Some(Err(err)) => {
+            tracing::warn!("ignoring {}: {}", CALENDAR_FILE, err);
+            vec![]
+        }
+        None => vec![],
+    };
+
+    get_config()
+        .file_config
+        .overrides
+        .iter()
+        .chain(&source)
+        .find(|date_override| date_override.is_active(today))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn range(start: &str, end: Option<&str>, yearly: bool) -> DateOverride {
+        DateOverride {
+            start: start.to_string(),
+            end: end.map(String::from),
+            wallpaper: Some("a.png".to_string()),
+            tag: None,
+            yearly,
+        }
+    }
+
+    #[test]
+    fn test_is_active() {
+        let single = range("2024-06-01", None, false);
+        assert!(single.is_active(date("2024-06-01")));
+        assert!(!single.is_active(date("2024-06-02")));
+        assert!(!single.is_active(date("2025-06-01")));
+
+        let yearly = range("2024-06-01", None, true);
+        assert!(yearly.is_active(date("2030-06-01")));
+
+        let month_day = range("06-01", Some("06-03"), false);
+        assert!(month_day.is_active(date("2025-06-03")));
+        assert!(!month_day.is_active(date("2025-06-04")));
+
+        let new_year = range("12-24", Some("01-01"), true);
+        assert!(new_year.is_active(date("2024-12-31")));
+        assert!(new_year.is_active(date("2025-01-01")));
+        assert!(!new_year.is_active(date("2025-01-02")));
+
+        let leap_day = range("02-29", None, true);
+        assert!(leap_day.is_active(date("2028-02-29")));
+        assert!(!leap_day.is_active(date("2027-03-01")));
+
+        assert!(!range("2024-02-30", None, false).is_active(date("2024-02-29")));
+        assert!(!range("someday", None, true).is_active(date("2024-02-29")));
+    }
+
+    #[test]
+    fn test_parse() {
+        let overrides = parse(
+            r#"
+            [[overrides]]
+            date = "12-25"
+            tag = "christmas"
+
+            [[overrides]]
+            start = "2024-09-01"
+            end = "2024-09-07"
+            wallpaper = "team/offsite.png"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides[0].start, "12-25");
+        assert_eq!(overrides[0].tag.as_deref(), Some("christmas"));
+        assert_eq!(overrides[1].end.as_deref(), Some("2024-09-07"));
+        assert!(parse("").unwrap().is_empty());
+    }
+}
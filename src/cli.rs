@@ -0,0 +1,438 @@
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
+use clap::{Parser, Subcommand};
+
+use crate::get_config;
+use crate::playlists;
+use crate::selection;
+use crate::store::{Pause, Store};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// Autostart mirai on boot
+    #[cfg(target_os = "windows")]
+    #[arg(long, action)]
+    pub autostart: Option<bool>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Keep the current wallpaper until resumed
+    Pause {
+        /// Resume automatically after this long, e.g. "45m" or "1h30m"
+        #[arg(long = "for", value_parser = parse_duration, conflicts_with = "until")]
+        duration: Option<TimeDelta>,
+
+        /// Resume automatically at this time, "HH:MM" or "YYYY-MM-DD HH:MM"
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Resume wallpaper changes
+    Resume,
+    /// Mark a wallpaper as a favorite so it is picked more often
+    Favorite {
+        /// Wallpaper id, defaults to the current wallpaper
+        id: Option<String>,
+    },
+    /// Remove a wallpaper from the favorites
+    Unfavorite {
+        /// Wallpaper id, defaults to the current wallpaper
+        id: Option<String>,
+    },
+    /// Never show a wallpaper again, the current one is replaced right away
+    Ban {
+        /// Wallpaper id, defaults to the current wallpaper
+        id: Option<String>,
+    },
+    /// Put a banned wallpaper back into rotation
+    Unban { id: String },
+    /// List banned wallpapers
+    Bans,
+    /// Switch between playlists
+    Playlist {
+        #[command(subcommand)]
+        command: PlaylistCommand,
+    },
+    /// Manage the tags of a wallpaper
+    Tag {
+        #[command(subcommand)]
+        command: TagCommand,
+    },
+    /// Rate a wallpaper from 1 to 5 stars, higher rated wallpapers are picked more often
+    Rate {
+        /// Stars, 0 clears the rating
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=5))]
+        stars: u8,
+
+        /// Wallpaper id, defaults to the current wallpaper
+        id: Option<String>,
+    },
+    /// Skip to another wallpaper, a wallpaper skipped soon after being shown is picked less often
+    Next,
+    /// List wallpapers with their rating, skips and the score they are picked by
+    List,
+    /// Set how likely a wallpaper is to be picked, 1 is the default and 0 never picks it
+    Weight {
+        #[arg(value_parser = parse_weight)]
+        weight: f64,
+
+        /// Wallpaper id, defaults to the current wallpaper
+        id: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PlaylistCommand {
+    /// List the playlists, marking the active one
+    List,
+    /// Only show the wallpapers of a playlist, starting right away
+    Use { name: String },
+    /// Go back to showing all wallpapers
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TagCommand {
+    /// Tag a wallpaper, the tag is kept across rescans
+    Add {
+        tag: String,
+
+        /// Wallpaper id, defaults to the current wallpaper
+        id: Option<String>,
+    },
+    /// Remove a tag added with `tag add`
+    Remove {
+        tag: String,
+
+        /// Wallpaper id, defaults to the current wallpaper
+        id: Option<String>,
+    },
+    /// List the tags of a wallpaper and where they come from
+    List {
+        /// Wallpaper id, defaults to the current wallpaper
+        id: Option<String>,
+    },
+}
+
+/// Runs a command against the store of the running daemon, which picks up
+/// the change on its next check
+pub fn run(command: Command) -> Result<(), String> {
+    let store = Store::new().map_err(|err| err.to_string())?;
+
+    match command {
+        Command::Pause { duration, until } => {
+            let now = store.clock().now();
+            let pause = match (duration, until) {
+                (Some(duration), _) => Pause::Until(
+                    now.checked_add_signed(duration)
+                        .ok_or_else(|| "pause duration is too long".to_string())?,
+                ),
+                (None, Some(until)) => Pause::Until(parse_until(&until, now)?),
+                (None, None) => Pause::Indefinite,
+            };
+
+            match &pause {
+                Pause::Indefinite => println!("paused until resumed"),
+                Pause::Until(until) => println!("paused until {}", until.format("%Y-%m-%d %H:%M")),
+            }
+            store.set_pause(Some(pause));
+        }
+        Command::Resume => {
+            store.set_pause(None);
+            println!("resumed");
+        }
+        Command::Favorite { id } => {
+            let id = wallpaper_id(&store, id)?;
+            store
+                .set_favorite(&id, true)
+                .map_err(|err| err.to_string())?;
+            println!("added {id} to favorites");
+        }
+        Command::Unfavorite { id } => {
+            let id = wallpaper_id(&store, id)?;
+            store
+                .set_favorite(&id, false)
+                .map_err(|err| err.to_string())?;
+            println!("removed {id} from favorites");
+        }
+        Command::Ban { id } => {
+            let current = store.get_meta().map(|meta| meta.last_used);
+            let id = wallpaper_id(&store, id)?;
+            store.set_banned(&id, true).map_err(|err| err.to_string())?;
+            if current.as_ref() == Some(&id) {
+                store.clear_last_update();
+            }
+            println!("banned {id}");
+        }
+        Command::Unban { id } => {
+            store
+                .set_banned(&id, false)
+                .map_err(|err| err.to_string())?;
+            println!("unbanned {id}");
+        }
+        Command::Bans => {
+            for wallpaper in store.get_banned_wallpapers() {
+                println!("{}", wallpaper.id);
+            }
+        }
+        Command::Playlist { command } => run_playlist(&store, command)?,
+        Command::Tag { command } => run_tag(&store, command)?,
+        Command::Rate { stars, id } => {
+            let id = wallpaper_id(&store, id)?;
+            let rating = (stars > 0).then_some(stars);
+            store
+                .set_rating(&id, rating)
+                .map_err(|err| err.to_string())?;
+            match rating {
+                Some(stars) => println!("rated {id} {stars}/5"),
+                None => println!("cleared the rating of {id}"),
+            }
+        }
+        Command::Next => {
+            if let Some(id) = store.record_skip(skip_window()) {
+                println!("skipped {id}");
+            }
+            store.clear_last_update();
+            if store.get_pause().is_some() {
+                println!("paused, the wallpaper changes once resumed");
+            }
+        }
+        Command::List => {
+            let config = &get_config().file_config.selection;
+            let mut wallpapers = store.get_inserted_wallpapers();
+            wallpapers.sort_by(|a, b| a.id.cmp(&b.id));
+
+            println!("score  rating  shown  skipped  wallpaper");
+            for wallpaper in wallpapers {
+                let score = if wallpaper.banned {
+                    "banned".to_string()
+                } else {
+                    format!("{:.2}", selection::effective_weight(&wallpaper, config))
+                };
+                let rating = wallpaper
+                    .rating
+                    .map_or("-".to_string(), |rating| format!("{rating}/5"));
+                let favorite = if wallpaper.favorite { " *" } else { "" };
+                println!(
+                    "{score:<6} {rating:<7} {:<6} {:<8} {}{favorite}",
+                    wallpaper.shows, wallpaper.skips, wallpaper.id
+                );
+            }
+        }
+        Command::Weight { weight, id } => {
+            let id = wallpaper_id(&store, id)?;
+            store
+                .set_weight(&id, weight)
+                .map_err(|err| err.to_string())?;
+            println!("set weight of {id} to {weight}");
+        }
+    }
+
+    Ok(())
+}
+
+fn run_playlist(store: &Store, command: PlaylistCommand) -> Result<(), String> {
+    match command {
+        PlaylistCommand::List => {
+            let active = store.get_active_playlist();
+            for (name, playlist) in playlists::all(store) {
+                let marker = if active.as_ref() == Some(&name) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{marker} {name} ({} wallpapers)", playlist.wallpapers.len());
+            }
+        }
+        PlaylistCommand::Use { name } => {
+            if !playlists::all(store).contains_key(&name) {
+                return Err(format!("no playlist named {name}"));
+            }
+            store.set_active_playlist(Some(&name));
+            store.clear_last_update();
+            println!("using playlist {name}");
+        }
+        PlaylistCommand::Clear => {
+            store.set_active_playlist(None);
+            store.clear_last_update();
+            println!("using all wallpapers");
+        }
+    }
+
+    Ok(())
+}
+
+fn run_tag(store: &Store, command: TagCommand) -> Result<(), String> {
+    match command {
+        TagCommand::Add { tag, id } => {
+            let id = wallpaper_id(store, id)?;
+            store.add_tag(&id, &tag).map_err(|err| err.to_string())?;
+            println!("tagged {id} with {tag}");
+        }
+        TagCommand::Remove { tag, id } => {
+            let id = wallpaper_id(store, id)?;
+            if store.remove_tag(&id, &tag).map_err(|err| err.to_string())? {
+                println!("removed {tag} from {id}");
+            } else if store
+                .get_tags(&id)
+                .iter()
+                .any(|found| found.name == tag.to_lowercase())
+            {
+                return Err(format!(
+                    "{tag} comes from a directory or tag file, change it there"
+                ));
+            } else {
+                return Err(format!("{id} is not tagged with {tag}"));
+            }
+        }
+        TagCommand::List { id } => {
+            let id = wallpaper_id(store, id)?;
+            for tag in store.get_tags(&id) {
+                println!("{} ({})", tag.name, tag.source);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// a wallpaper skipped within the first half of its slot counts as skipped soon after being shown
+fn skip_window() -> TimeDelta {
+    TimeDelta::minutes(get_config().file_config.update_interval.into()) / 2
+}
+
+// the given wallpaper, or the one currently shown
+fn wallpaper_id(store: &Store, id: Option<String>) -> Result<String, String> {
+    match id {
+        Some(id) => Ok(id),
+        None => store
+            .get_meta()
+            .map(|meta| meta.last_used)
+            .ok_or_else(|| "no wallpaper has been set yet, pass an id".to_string()),
+    }
+}
+
+fn parse_weight(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(weight) if weight.is_finite() && weight >= 0.0 => Ok(weight),
+        _ => Err(format!(
+            "invalid weight: {value}, expected a number of at least 0"
+        )),
+    }
+}
+
+fn parse_duration(value: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("invalid duration: {value}");
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+
+    for char in value.trim().chars() {
+        if char.is_ascii_digit() {
+            number.push(char);
+            continue;
+        }
+
+        let amount: i64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+
+        let part = match char {
+            'd' => TimeDelta::try_days(amount),
+            'h' => TimeDelta::try_hours(amount),
+            'm' => TimeDelta::try_minutes(amount),
+            's' => TimeDelta::try_seconds(amount),
+            _ => return Err(format!("invalid duration unit '{char}' in {value}")),
+        };
+        total = part
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(invalid)?;
+    }
+
+    // a bare number is in minutes, like update_interval
+    if !number.is_empty() {
+        let amount: i64 = number.parse().map_err(|_| invalid())?;
+        total = TimeDelta::try_minutes(amount)
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(invalid)?;
+    }
+
+    if total <= TimeDelta::zero() {
+        return Err(invalid());
+    }
+
+    Ok(total)
+}
+
+/// Parses a time to resume at, a bare time means its next occurrence
+fn parse_until(value: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let naive = match NaiveTime::parse_from_str(value, "%H:%M") {
+        Ok(time) => {
+            let today = now.date_naive().and_time(time);
+            if today > now.naive_local() {
+                today
+            } else {
+                today + TimeDelta::days(1)
+            }
+        }
+        Err(_) => NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M")
+            .map_err(|_| format!("invalid time: {value}, expected HH:MM or YYYY-MM-DD HH:MM"))?,
+    };
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("{value} does not exist in the local timezone"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45m"), Ok(TimeDelta::minutes(45)));
+        assert_eq!(parse_duration("90"), Ok(TimeDelta::minutes(90)));
+        assert_eq!(
+            parse_duration("1h30m"),
+            Ok(TimeDelta::hours(1) + TimeDelta::minutes(30))
+        );
+        assert_eq!(parse_duration("2d"), Ok(TimeDelta::days(2)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("10x").is_err());
+        // too long for a TimeDelta, rather than panicking
+        assert!(parse_duration("99999999999999d").is_err());
+        assert!(parse_duration("9223372036854775807s").is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_parse_weight() {
+        assert_eq!(parse_weight("2.5"), Ok(2.5));
+        assert_eq!(parse_weight("0"), Ok(0.0));
+        assert!(parse_weight("-1").is_err());
+        assert!(parse_weight("inf").is_err());
+        assert!(parse_weight("heavy").is_err());
+    }
+
+    #[test]
+    fn test_parse_until() {
+        let now = Local
+            .with_ymd_and_hms(2024, 6, 1, 12, 0, 0)
+            .earliest()
+            .unwrap();
+
+        let later_today = parse_until("18:30", now).unwrap();
+        assert_eq!(later_today.naive_local().to_string(), "2024-06-01 18:30:00");
+
+        let tomorrow = parse_until("09:00", now).unwrap();
+        assert_eq!(tomorrow.naive_local().to_string(), "2024-06-02 09:00:00");
+
+        let date = parse_until("2024-06-10 08:00", now).unwrap();
+        assert_eq!(date.naive_local().to_string(), "2024-06-10 08:00:00");
+
+        assert!(parse_until("tomorrow", now).is_err());
+    }
+}
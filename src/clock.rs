@@ -0,0 +1,166 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, TimeDelta};
+
+/// Source of the current time, so scheduling can be driven by a fake clock in tests
+pub trait Clock {
+    fn now(&self) -> DateTime<Local>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// How far wall time and monotonic time can drift apart between two ticks
+/// before it counts as a jump
+const JUMP_THRESHOLD: Duration = Duration::from_secs(30);
+
+#[derive(Debug, PartialEq)]
+pub enum TimeJump {
+    /// Wall time moved further than the loop did, after a suspend or when
+    /// the clock was set forward
+    Forward(TimeDelta),
+    /// The clock was set backwards
+    Backward(TimeDelta),
+}
+
+/// Notices suspends and wall clock changes during a sleep of the main loop by
+/// comparing how far wall time and monotonic time moved
+pub struct JumpDetector {
+    wall: DateTime<Local>,
+    monotonic: Instant,
+    slept: Duration,
+}
+
+impl JumpDetector {
+    /// Marks the start of a sleep that should last `slept`
+    pub fn new(wall: DateTime<Local>, monotonic: Instant, slept: Duration) -> JumpDetector {
+        JumpDetector {
+            wall,
+            monotonic,
+            slept,
+        }
+    }
+
+    pub fn check(&self, wall: DateTime<Local>, monotonic: Instant) -> Option<TimeJump> {
+        let wall_elapsed = wall - self.wall;
+        let monotonic_elapsed = monotonic.duration_since(self.monotonic);
+
+        let threshold = TimeDelta::from_std(JUMP_THRESHOLD).ok()?;
+        let drift = wall_elapsed - TimeDelta::from_std(monotonic_elapsed).ok()?;
+        if drift > threshold {
+            return Some(TimeJump::Forward(drift));
+        }
+        if drift < -threshold {
+            return Some(TimeJump::Backward(-drift));
+        }
+
+        // monotonic time keeps counting during suspend on some platforms,
+        // then only the overslept sleep gives it away
+        if monotonic_elapsed > self.slept + JUMP_THRESHOLD {
+            return Some(TimeJump::Forward(wall_elapsed));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+pub use fake::FakeClock;
+
+#[cfg(test)]
+mod fake {
+    use std::cell::Cell;
+
+    use chrono::{DateTime, Local, TimeDelta};
+
+    use super::Clock;
+
+    /// Clock that only moves when told to
+    pub struct FakeClock {
+        now: Cell<DateTime<Local>>,
+    }
+
+    impl FakeClock {
+        pub fn new(now: DateTime<Local>) -> FakeClock {
+            FakeClock {
+                now: Cell::new(now),
+            }
+        }
+
+        pub fn set(&self, now: DateTime<Local>) {
+            self.now.set(now);
+        }
+
+        pub fn advance(&self, delta: TimeDelta) {
+            self.now.set(self.now.get() + delta);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> DateTime<Local> {
+            self.now.get()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLEPT: Duration = Duration::from_secs(5);
+
+    fn start() -> (JumpDetector, DateTime<Local>, Instant) {
+        let wall = Local::now();
+        let monotonic = Instant::now();
+        (JumpDetector::new(wall, monotonic, SLEPT), wall, monotonic)
+    }
+
+    #[test]
+    fn test_regular_sleep_is_not_a_jump() {
+        let (detector, wall, monotonic) = start();
+        assert_eq!(
+            detector.check(
+                wall + TimeDelta::seconds(5),
+                monotonic + SLEPT + Duration::from_millis(20)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_suspend_is_a_forward_jump() {
+        // monotonic time stopped while suspended
+        let (detector, wall, monotonic) = start();
+        assert_eq!(
+            detector.check(wall + TimeDelta::hours(2), monotonic + SLEPT),
+            Some(TimeJump::Forward(
+                TimeDelta::hours(2) - TimeDelta::seconds(5)
+            ))
+        );
+
+        // monotonic time kept going while suspended
+        assert_eq!(
+            detector.check(
+                wall + TimeDelta::hours(1),
+                monotonic + Duration::from_secs(3600)
+            ),
+            Some(TimeJump::Forward(TimeDelta::hours(1)))
+        );
+    }
+
+    #[test]
+    fn test_clock_set_backwards() {
+        let (detector, wall, monotonic) = start();
+        assert_eq!(
+            detector.check(wall - TimeDelta::minutes(10), monotonic + SLEPT),
+            Some(TimeJump::Backward(
+                TimeDelta::minutes(10) + TimeDelta::seconds(5)
+            ))
+        );
+    }
+}
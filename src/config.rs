@@ -8,7 +8,12 @@ use std::fs;
 use std::path::Path;
 use tracing::Level;
 
+use crate::backends::BackendSelection;
+use crate::calendar::DateOverride;
 use crate::content_managers::ContentManagerTypes;
+use crate::playlists::Playlists;
+use crate::seasons::SeasonalCollections;
+use crate::tags::TagFilter;
 
 #[cfg(not(test))]
 const CONFIG_NAME: &str = "mirai.toml";
@@ -29,24 +34,74 @@ impl Display for Config {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct FileConfig {
     pub update_interval: u32,
+    // seconds between backend health checks
+    #[serde(default = "default_health_check_interval")]
+    pub health_check_interval: u64,
     pub log_level: Option<LogLevel>,
     pub content_manager_type: ContentManagerTypes,
+    #[serde(default)]
+    pub backend: BackendSelection,
     pub local: LocalWallpaperConfig,
     pub git: GitWallpaperConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub selection: SelectionConfig,
+    #[serde(default)]
+    pub tags: TagFilter,
+    #[serde(default)]
+    pub seasonal: SeasonalCollections,
+    #[serde(default)]
+    pub swaybg: SwaybgBackendConfig,
+    #[serde(default)]
+    pub hyprpaper: HyprpaperBackendConfig,
+    #[serde(default)]
+    pub feh: FehBackendConfig,
+    #[serde(default)]
+    pub xwallpaper: XWallpaperBackendConfig,
+    #[serde(default)]
+    pub gnome: GnomeBackendConfig,
+    #[serde(default)]
+    pub plasma: PlasmaBackendConfig,
+    #[serde(default)]
+    pub command: CommandBackendConfig,
+    #[serde(default)]
+    pub playlists: Playlists,
+    #[serde(default)]
+    pub overrides: Vec<DateOverride>,
 }
 
 impl Default for FileConfig {
     fn default() -> Self {
         FileConfig {
             update_interval: 1440,
+            health_check_interval: default_health_check_interval(),
             content_manager_type: ContentManagerTypes::Local,
+            backend: BackendSelection::default(),
             local: LocalWallpaperConfig::default(),
             log_level: None,
             git: GitWallpaperConfig::default(),
+            retry: RetryConfig::default(),
+            selection: SelectionConfig::default(),
+            tags: TagFilter::default(),
+            seasonal: SeasonalCollections::default(),
+            swaybg: SwaybgBackendConfig::default(),
+            hyprpaper: HyprpaperBackendConfig::default(),
+            feh: FehBackendConfig::default(),
+            xwallpaper: XWallpaperBackendConfig::default(),
+            gnome: GnomeBackendConfig::default(),
+            plasma: PlasmaBackendConfig::default(),
+            command: CommandBackendConfig::default(),
+            playlists: Playlists::default(),
+            overrides: vec![],
         }
     }
 }
 
+fn default_health_check_interval() -> u64 {
+    5
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct LogLevel(pub Level);
 
@@ -129,12 +184,275 @@ impl Default for GitWallpaperConfig {
     }
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    // seconds before the first retry
+    pub initial_delay: u64,
+    // upper bound in seconds for the delay between retries
+    pub max_delay: u64,
+    #[serde(deserialize_with = "deserialize_multiplier")]
+    pub multiplier: f64,
+    // attempts per wallpaper change before giving up until the next one
+    pub attempts: u32,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            initial_delay: 5,
+            max_delay: 300,
+            multiplier: 2.0,
+            attempts: 5,
+        }
+    }
+}
+
+// a multiplier below 1 would shrink the delays instead of backing off
+fn deserialize_multiplier<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let multiplier = f64::deserialize(deserializer)?;
+    if multiplier.is_finite() && multiplier >= 1.0 {
+        Ok(multiplier)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "retry multiplier must be at least 1, got {}",
+            multiplier
+        )))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SelectionConfig {
+    pub strategy: SelectionStrategy,
+    // walk the order of the sequential strategies backwards
+    pub reverse: bool,
+    // how many of the last shown wallpapers can't be picked again, even
+    // after all wallpapers have been seen
+    pub no_repeat_window: u32,
+    // shared by all instances that should pick the same wallpapers
+    pub seed: String,
+    // how strongly weights bias the random pick, 0 ignores them and higher
+    // values favor heavier wallpapers more
+    pub weight_bias: f64,
+    // weight multiplier for favorited wallpapers
+    pub favorite_weight: f64,
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        SelectionConfig {
+            strategy: SelectionStrategy::default(),
+            reverse: false,
+            no_repeat_window: 1,
+            seed: String::new(),
+            weight_bias: 1.0,
+            favorite_weight: 3.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionStrategy {
+    // weighted random pick among the unseen wallpapers
+    #[default]
+    Random,
+    Name,
+    // file modification time, oldest first
+    Modified,
+    // date of the last commit touching the file, oldest first
+    CommitDate,
+    // random order that stays the same for a whole cycle
+    Shuffle,
+    // derived from the seed and the schedule slot, so instances sharing a
+    // seed and a wallpaper source show the same wallpaper
+    Seeded,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SwaybgBackendConfig {
+    pub mode: SwaybgMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwaybgMode {
+    Center,
+    #[default]
+    Fill,
+    Fit,
+    Stretch,
+    Tile,
+}
+
+impl SwaybgMode {
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            SwaybgMode::Center => "center",
+            SwaybgMode::Fill => "fill",
+            SwaybgMode::Fit => "fit",
+            SwaybgMode::Stretch => "stretch",
+            SwaybgMode::Tile => "tile",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HyprpaperBackendConfig {
+    // monitors to apply the wallpaper to, all monitors when empty
+    pub monitors: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct FehBackendConfig {
+    pub mode: FehMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FehMode {
+    Center,
+    #[default]
+    Fill,
+    Max,
+    Scale,
+    Tile,
+}
+
+impl FehMode {
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            FehMode::Center => "--bg-center",
+            FehMode::Fill => "--bg-fill",
+            FehMode::Max => "--bg-max",
+            FehMode::Scale => "--bg-scale",
+            FehMode::Tile => "--bg-tile",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct XWallpaperBackendConfig {
+    pub mode: XWallpaperMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XWallpaperMode {
+    Center,
+    Maximize,
+    Stretch,
+    Tile,
+    #[default]
+    Zoom,
+}
+
+impl XWallpaperMode {
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            XWallpaperMode::Center => "--center",
+            XWallpaperMode::Maximize => "--maximize",
+            XWallpaperMode::Stretch => "--stretch",
+            XWallpaperMode::Tile => "--tile",
+            XWallpaperMode::Zoom => "--zoom",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct GnomeBackendConfig {
+    pub mode: GnomeMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GnomeMode {
+    Centered,
+    Scaled,
+    Spanned,
+    Stretched,
+    Wallpaper,
+    #[default]
+    Zoom,
+}
+
+impl GnomeMode {
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            GnomeMode::Centered => "centered",
+            GnomeMode::Scaled => "scaled",
+            GnomeMode::Spanned => "spanned",
+            GnomeMode::Stretched => "stretched",
+            GnomeMode::Wallpaper => "wallpaper",
+            GnomeMode::Zoom => "zoom",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PlasmaBackendConfig {
+    pub mode: PlasmaMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlasmaMode {
+    Center,
+    #[default]
+    Crop,
+    Fit,
+    Stretch,
+    Tile,
+}
+
+impl PlasmaMode {
+    // values of the QtQuick Image.FillMode enum used by the org.kde.image plugin
+    pub fn as_fill_mode(&self) -> u8 {
+        match self {
+            PlasmaMode::Stretch => 0,
+            PlasmaMode::Fit => 1,
+            PlasmaMode::Crop => 2,
+            PlasmaMode::Tile => 3,
+            PlasmaMode::Center => 6,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct CommandBackendConfig {
+    // supports the {path}, {id}, {source} and {output} placeholders
+    pub set: String,
+    // considered ready when this exits successfully, always ready when unset
+    pub ready: Option<String>,
+    // the set command is run once per output
+    #[serde(default)]
+    pub outputs: Vec<String>,
+}
+
 #[cfg(test)]
 impl Config {
     pub fn create_config() -> Config {
         Config {
             data_dir: "/tmp/mirai".into(),
-            file_config: FileConfig::default(),
+            file_config: FileConfig {
+                local: LocalWallpaperConfig {
+                    path: std::env::temp_dir()
+                        .join("mirai-wallpapers")
+                        .to_string_lossy()
+                        .to_string(),
+                },
+                // tests should not wait between retries
+                retry: RetryConfig {
+                    initial_delay: 0,
+                    max_delay: 0,
+                    ..RetryConfig::default()
+                },
+                ..FileConfig::default()
+            },
         }
     }
 }
@@ -181,3 +499,28 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_multiplier_is_validated() {
+        let retry =
+            |multiplier: &str| toml::from_str::<RetryConfig>(&format!("multiplier = {multiplier}"));
+
+        assert_eq!(retry("1.5").unwrap().multiplier, 1.5);
+        assert!(retry("0.5").is_err());
+        assert!(retry("-2.0").is_err());
+        assert!(retry("nan").is_err());
+    }
+
+    #[test]
+    fn test_command_backend_only_needs_set() {
+        let command: CommandBackendConfig = toml::from_str("set = \"setbg {path}\"").unwrap();
+
+        assert_eq!(command.set, "setbg {path}");
+        assert!(command.ready.is_none());
+        assert!(command.outputs.is_empty());
+    }
+}
@@ -1,14 +1,18 @@
 use rand::RngExt;
 use rand::distr::Alphanumeric;
+use std::collections::HashMap;
 use std::fs;
-use std::io::BufRead;
 use std::path::PathBuf;
 use std::process::{Command, Output};
 use thiserror::Error;
 
+use crate::config::SelectionStrategy;
 use crate::content_managers::ContentManagerTypes;
 use crate::get_config;
-use crate::wallpaper::{Wallpaper, WallpaperContentManager, WallpaperContentManagerError};
+use crate::tags;
+use crate::wallpaper::{
+    SourceScan, Wallpaper, WallpaperContentManager, WallpaperContentManagerError,
+};
 
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
@@ -27,33 +31,59 @@ impl GitContentManager {
 }
 
 impl WallpaperContentManager for GitContentManager {
-    fn get_wallpapers(&self) -> Result<Vec<Wallpaper>, WallpaperContentManagerError> {
+    fn get_wallpapers(&self) -> Result<SourceScan, WallpaperContentManagerError> {
         let temp_repo = GitTempRepo::new().map_err(|_| WallpaperContentManagerError::Failure)?;
-        temp_repo
-            .clone_repo()
-            .map_err(|_| WallpaperContentManagerError::Failure)?;
+        // commit dates need the history, which the default shallow clone leaves out
+        let needs_history =
+            get_config().file_config.selection.strategy == SelectionStrategy::CommitDate;
+        if needs_history {
+            temp_repo.clone_history()
+        } else {
+            temp_repo.clone_repo()
+        }
+        .map_err(|_| WallpaperContentManagerError::Failure)?;
         temp_repo
             .sparse_checkout_2()
             .map_err(|_| WallpaperContentManagerError::Failure)?;
 
         // TODO: improve config validation
         let wallpapers_path = get_config().file_config.git.path.clone().unwrap();
-        let wallpapers = temp_repo
+        let files = temp_repo
             .ls_tree(wallpapers_path.as_str())
             .unwrap_or_else(|err| {
                 tracing::error!("error while settign wallpaper: {}", err);
                 vec![]
             });
 
-        tracing::info!("found {} wallpapers in git repo", wallpapers.len());
+        tracing::info!("found {} wallpapers in git repo", files.len());
+
+        let commit_dates = if needs_history {
+            temp_repo
+                .commit_dates(wallpapers_path.as_str())
+                .unwrap_or_else(|err| {
+                    tracing::error!("failed to get commit dates: {}", err);
+                    HashMap::new()
+                })
+        } else {
+            HashMap::new()
+        };
 
-        Ok(wallpapers
-            .iter()
-            .map(|wallpaper| Wallpaper {
-                id: wallpaper.to_string(),
-                type_id: ContentManagerTypes::Git,
+        // tag, playlist and calendar files are read straight from the
+        // repository, fetching only them
+        let read_file = |file: &str| temp_repo.show_file(&source_path(&wallpapers_path, file));
+
+        let ids = files.iter().map(|(file, _)| file.clone()).collect();
+        let hashes = files.into_iter().collect::<HashMap<_, _>>();
+        let wallpapers = tags::tag_wallpapers(ids, read_file)
+            .into_iter()
+            .map(|(id, tags)| Wallpaper {
+                committed: commit_dates.get(&id).copied(),
+                hash: hashes.get(&id).cloned(),
+                tags,
+                ..Wallpaper::new(id, ContentManagerTypes::Git)
             })
-            .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        Ok(SourceScan::new(wallpapers, read_file))
     }
 
     fn cleanup_wallpaper(&self, wallpaper: Wallpaper) -> bool {
@@ -107,6 +137,8 @@ pub enum GitTempRepoError {
     NoHeadRev,
     #[error("failed get files in head rev")]
     NoFiles,
+    #[error("failed to read the commit history")]
+    NoHistory,
     #[error("failed to create temp directory for repo: {0}")]
     IoError(String),
 }
@@ -146,8 +178,46 @@ impl GitTempRepo {
         Ok(())
     }
 
+    // full history without file contents, enough for git log
+    pub fn clone_history(&self) -> Result<(), GitTempRepoError> {
+        self.run(&format!(
+            "git clone -n --filter=blob:none {} .",
+            self.repo_url
+        ))
+        .map_err(|_| GitTempRepoError::CloneFailure)?;
+
+        Ok(())
+    }
+
+    /// Unix time of the last commit touching each file under `path`
+    pub fn commit_dates(&self, path: &str) -> Result<HashMap<String, i64>, GitTempRepoError> {
+        let output = self
+            .run("git log --format=@%ct --name-only HEAD")
+            .map_err(|_| GitTempRepoError::NoHistory)?;
+        if !output.status.success() {
+            return Err(GitTempRepoError::NoHistory);
+        }
+
+        Ok(parse_commit_dates(
+            &String::from_utf8_lossy(&output.stdout),
+            path,
+        ))
+    }
+
+    /// Contents of a file at HEAD, if it exists
+    pub fn show_file(&self, file: &str) -> Option<String> {
+        let output = self
+            .run_args(&["git", "show", &format!("HEAD:{file}")])
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        String::from_utf8(output.stdout).ok()
+    }
+
     pub fn sparse_checkout(&self, file_path: &str) -> Result<(), GitTempRepoError> {
-        self.run(&format!("git sparse-checkout set --no-cone {}", file_path))
+        self.run_args(&["git", "sparse-checkout", "set", "--no-cone", file_path])
             .map_err(|_| GitTempRepoError::CheckoutFailure)?;
         Ok(())
     }
@@ -158,7 +228,9 @@ impl GitTempRepo {
         Ok(())
     }
 
-    pub fn ls_tree(&self, path: &str) -> Result<Vec<String>, GitTempRepoError> {
+    /// Files under `path` with the id of their blob, which is the same
+    /// content hash `hash::blob_id` computes for local files
+    pub fn ls_tree(&self, path: &str) -> Result<Vec<(String, String)>, GitTempRepoError> {
         let rev_output = self
             .run("git rev-parse HEAD")
             .map_err(|_| GitTempRepoError::NoHeadRev)?;
@@ -168,23 +240,13 @@ impl GitTempRepo {
         tracing::debug!("using {} as HEAD rev", rev);
 
         let output = self
-            .run(&format!("git ls-tree -r {} --name-only", &rev))
+            .run(&format!("git ls-tree -r {}", &rev))
             .map_err(|_| GitTempRepoError::NoFiles)?;
-        let files = output
-            .stdout
-            .lines()
-            .filter_map(|file| {
-                if let Ok(file) = file
-                    && file.starts_with(path)
-                {
-                    return Some(file.replace(path, "").trim_start_matches("/").to_string());
-                }
 
-                None
-            })
-            .collect::<Vec<_>>();
-
-        Ok(files)
+        Ok(parse_ls_tree(
+            &String::from_utf8_lossy(&output.stdout),
+            path,
+        ))
     }
 
     pub fn checkout(&self) -> Result<(), GitTempRepoError> {
@@ -194,12 +256,14 @@ impl GitTempRepo {
     }
 
     fn run(&self, command: &str) -> Result<Output, ()> {
-        let parts = command.split_whitespace().collect::<Vec<_>>();
-        let cmd = parts[0];
-        let args = &parts[1..parts.len()];
+        self.run_args(&command.split_whitespace().collect::<Vec<_>>())
+    }
 
-        let mut cmd = Command::new(cmd);
-        cmd.args(args).current_dir(&self.path);
+    // for arguments that may contain whitespace, such as file paths
+    fn run_args(&self, parts: &[&str]) -> Result<Output, ()> {
+        let command = parts.join(" ");
+        let mut cmd = Command::new(parts[0]);
+        cmd.args(&parts[1..]).current_dir(&self.path);
 
         #[cfg(windows)]
         {
@@ -218,3 +282,107 @@ impl GitTempRepo {
         Ok(output)
     }
 }
+
+// path of a file in the repo relative to the wallpapers path, if it is under it
+fn relative_path(file: &str, path: &str) -> Option<String> {
+    if file.starts_with(path) {
+        return Some(file.replace(path, "").trim_start_matches("/").to_string());
+    }
+
+    None
+}
+
+// lines of `git ls-tree -r` are "<mode> <type> <object id>\t<path>"
+fn parse_ls_tree(output: &str, path: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (info, file) = line.split_once('\t')?;
+            let object_id = info.split_whitespace().nth(2)?;
+            Some((relative_path(file, path)?, object_id.to_string()))
+        })
+        .collect()
+}
+
+// path in the repo of a file relative to the wallpapers path
+fn source_path(path: &str, file: &str) -> String {
+    match path.trim_end_matches('/') {
+        "" => file.to_string(),
+        path => format!("{path}/{file}"),
+    }
+}
+
+// git log lists commits newest first, so the first date seen for a file is its latest
+fn parse_commit_dates(log: &str, path: &str) -> HashMap<String, i64> {
+    let mut dates = HashMap::new();
+    let mut current = None;
+
+    for line in log.lines() {
+        if let Some(timestamp) = line.strip_prefix('@') {
+            current = timestamp.trim().parse::<i64>().ok();
+        } else if let (Some(date), Some(file)) = (current, relative_path(line, path)) {
+            dates.entry(file).or_insert(date);
+        }
+    }
+
+    dates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ls_tree() {
+        let output = "100644 blob 8ab686eafeb1f44702738c8b0f24f2567c36da6d\twallpapers/a.png\n100644 blob e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\tREADME.md\n100644 blob 8ab686eafeb1f44702738c8b0f24f2567c36da6d\twallpapers/nested/a copy.png\n";
+
+        assert_eq!(
+            parse_ls_tree(output, "wallpapers"),
+            [
+                (
+                    "a.png".to_string(),
+                    "8ab686eafeb1f44702738c8b0f24f2567c36da6d".to_string()
+                ),
+                (
+                    "nested/a copy.png".to_string(),
+                    "8ab686eafeb1f44702738c8b0f24f2567c36da6d".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_show_file_with_spaces() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("mirai-test-git-show-file");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("nested dir"))?;
+        fs::write(path.join("nested dir/a copy.png.tags"), "forest")?;
+        let repo = GitTempRepo {
+            path: path.clone(),
+            repo_url: String::new(),
+        };
+        repo.run("git init -q").unwrap();
+        repo.run("git add .").unwrap();
+        repo.run("git -c user.name=mirai -c user.email=mirai@localhost commit -q -m tags")
+            .unwrap();
+
+        assert_eq!(
+            repo.show_file("nested dir/a copy.png.tags").as_deref(),
+            Some("forest")
+        );
+        assert_eq!(repo.show_file("nested dir/missing.tags"), None);
+
+        fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_commit_dates() {
+        let log = "@1700000300\n\nwallpapers/b.png\n@1700000200\n\nwallpapers/a.png\nREADME.md\n@1700000100\n\nwallpapers/a.png\nwallpapers/b.png\n";
+
+        let dates = parse_commit_dates(log, "wallpapers");
+        assert_eq!(dates.len(), 2);
+        assert_eq!(dates["a.png"], 1700000200);
+        assert_eq!(dates["b.png"], 1700000300);
+    }
+}
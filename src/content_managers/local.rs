@@ -1,7 +1,13 @@
-use std::fs;
+use std::collections::HashMap;
+use std::fs::{self, DirEntry};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
 
 use crate::get_config;
-use crate::wallpaper::{Wallpaper, WallpaperContentManager, WallpaperContentManagerError};
+use crate::tags;
+use crate::wallpaper::{
+    SourceScan, Wallpaper, WallpaperContentManager, WallpaperContentManagerError,
+};
 
 use super::ContentManagerTypes;
 
@@ -19,17 +25,28 @@ impl LocalContentManager {
 }
 
 impl WallpaperContentManager for LocalContentManager {
-    fn get_wallpapers(&self) -> Result<Vec<Wallpaper>, WallpaperContentManagerError> {
-        Ok(fs::read_dir(get_config().file_config.local.path.clone())
-            .map_err(|_| WallpaperContentManagerError::Failure)?
-            .filter_map(Result::ok)
-            .filter(|entry| entry.file_type().map(|ft| ft.is_file()).unwrap_or(false))
-            .map(|path| {
-                let file_path = path.file_name().to_string_lossy().to_string();
-                tracing::trace!("found {}", file_path);
-                Wallpaper::new(file_path, ContentManagerTypes::Local)
+    fn get_wallpapers(&self) -> Result<SourceScan, WallpaperContentManagerError> {
+        let root = get_config().file_config.local.path.clone();
+        let mut files = HashMap::new();
+        list_files(Path::new(&root), "", &mut files)?;
+
+        let mut ids = files.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        let read_file = |file: &str| fs::read_to_string(Path::new(&root).join(file)).ok();
+
+        let wallpapers = tags::tag_wallpapers(ids, read_file)
+            .into_iter()
+            .map(|(id, tags)| {
+                tracing::trace!("found {}", id);
+                Wallpaper {
+                    // hashed when stored, which can skip unchanged files
+                    modified: files[&id],
+                    tags,
+                    ..Wallpaper::new(id, ContentManagerTypes::Local)
+                }
             })
-            .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        Ok(SourceScan::new(wallpapers, read_file))
     }
 
     // do not cleanup local wallpapers
@@ -37,3 +54,90 @@ impl WallpaperContentManager for LocalContentManager {
         false
     }
 }
+
+// collects files under `dir` by their `/` separated path relative to the root,
+// skipping hidden entries such as a .git directory
+fn list_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut HashMap<String, Option<i64>>,
+) -> Result<(), WallpaperContentManagerError> {
+    let entries = fs::read_dir(dir).map_err(|_| WallpaperContentManagerError::Failure)?;
+
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let id = format!("{prefix}{name}");
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                // an unreadable sub directory shouldn't hide the rest
+                let _ = list_files(&entry.path(), &format!("{id}/"), files);
+            }
+            Ok(file_type) if file_type.is_file() => {
+                files.insert(id, modified_time(&entry));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn modified_time(entry: &DirEntry) -> Option<i64> {
+    let modified = entry.metadata().ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use crate::tags::{Tag, TagSource};
+
+    use super::*;
+
+    #[test]
+    fn test_nested_wallpapers_and_tags() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join("mirai-test-local-scan");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("space/nebula"))?;
+        fs::create_dir_all(dir.join(".git"))?;
+        fs::write(dir.join("top.png"), "top")?;
+        fs::write(dir.join("space/nebula/orion.png"), "orion")?;
+        fs::write(dir.join("space/nebula/orion.png.tags"), "purple")?;
+        fs::write(dir.join(".git/HEAD"), "ref")?;
+
+        let mut files = HashMap::new();
+        list_files(&dir, "", &mut files)?;
+        let mut ids = files.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                "space/nebula/orion.png",
+                "space/nebula/orion.png.tags",
+                "top.png"
+            ]
+        );
+        assert!(files["top.png"].is_some());
+
+        let read_file = |file: &str| fs::read_to_string(dir.join(file)).ok();
+        let wallpapers = tags::tag_wallpapers(ids, read_file);
+        assert_eq!(wallpapers.len(), 2);
+        assert_eq!(
+            wallpapers[0].1,
+            [
+                Tag::new("space", TagSource::Directory),
+                Tag::new("nebula", TagSource::Directory),
+                Tag::new("purple", TagSource::Sidecar),
+            ]
+        );
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
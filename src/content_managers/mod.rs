@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use serde::{Deserialize, Serialize};
 
 pub mod git;
@@ -10,6 +12,16 @@ pub enum ContentManagerTypes {
     Git = 1,
 }
 
+impl Display for ContentManagerTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ContentManagerTypes::Local => "local",
+            ContentManagerTypes::Git => "git",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 impl From<ContentManagerTypes> for u8 {
     fn from(value: ContentManagerTypes) -> Self {
         value as u8
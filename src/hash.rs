@@ -0,0 +1,78 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Read};
+use std::path::Path;
+
+use sha1_smol::Sha1;
+
+/// Git's blob id of `len` bytes of content, the sha1 of a `blob <length>\0`
+/// header followed by the content. Local files are hashed the same way, so a
+/// wallpaper has the same hash in a local folder and a git repository. The
+/// content is read a chunk at a time rather than all at once
+pub fn blob_id(mut content: impl Read, len: u64) -> io::Result<String> {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", len).as_bytes());
+    let mut buffer = vec![0; 64 * 1024];
+    let mut read = 0;
+    loop {
+        match content.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => {
+                hasher.update(&buffer[..count]);
+                read += count as u64;
+            }
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    // the header has to match the content, e.g. a file changed while reading it
+    if read != len {
+        return Err(io::Error::other("content length changed while hashing it"));
+    }
+    Ok(hasher.digest().to_string())
+}
+
+pub fn file_blob_id(path: &Path) -> io::Result<String> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    blob_id(file, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_blob_id() -> Result<(), Box<dyn Error>> {
+        // as printed by `git hash-object`
+        assert_eq!(
+            blob_id(&b""[..], 0)?,
+            "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
+        );
+        assert_eq!(
+            blob_id(&b"hello\n"[..], 6)?,
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+        assert!(blob_id(&b"hello\n"[..], 5).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_blob_id() -> Result<(), Box<dyn Error>> {
+        let path = std::env::temp_dir().join("mirai-test-file-blob-id");
+        let contents = (0..200_000).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&path, &contents)?;
+
+        assert_eq!(
+            file_blob_id(&path)?,
+            blob_id(contents.as_slice(), contents.len() as u64)?
+        );
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
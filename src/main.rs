@@ -1,56 +1,77 @@
 #![cfg_attr(all(windows, not(debug_assertions)), windows_subsystem = "windows")]
 
 mod backends;
+mod calendar;
+mod cli;
+mod clock;
 mod config;
 mod content_managers;
+mod hash;
 mod log;
+mod playlists;
+mod retry;
+mod schedule;
+mod seasons;
+mod selection;
 mod store;
+mod tags;
 mod wallpaper;
 
-#[cfg(not(target_os = "windows"))]
-use self::backends::swww_cli::SwwCliBackend;
-
-use self::backends::WallpaperBackend;
+use self::backends::chain::BackendChain;
+use self::backends::{Backend, BackendTypes};
+use self::cli::Args;
+use self::clock::{JumpDetector, TimeJump};
 use self::config::{Config, LogLevel};
 use self::content_managers::ContentManagerTypes;
 use self::content_managers::git::GitContentManager;
 use self::content_managers::local::LocalContentManager;
+use self::retry::RetryPolicy;
 use self::store::Store;
-use self::wallpaper::{ContentManager, WallpapersManager};
-use chrono::{DateTime, Datelike, Local, TimeZone};
+use self::wallpaper::{ContentManager, LoopState, WallpapersManager};
 use once_cell::sync::OnceCell;
 use std::fs;
 #[cfg(target_os = "windows")]
 use std::fs::OpenOptions;
 use std::path::{Path, PathBuf};
 use std::thread::sleep;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tracing::Level;
 
-static MAX_BACKEND_READY_ATTEMPTS: i32 = 100;
-
 static CONFIG: OnceCell<Config> = OnceCell::new();
+// times the backend is checked at startup before giving up on it
+const MAX_BACKEND_READY_CHECKS: u32 = 100;
+
 pub fn get_config() -> &'static Config {
     CONFIG.get().expect("config is not yet initizlised")
 }
 
-// main function for windows which has the cli for
-// managing the service that is used on windows
-#[cfg(target_os = "windows")]
 fn main() -> Result<(), String> {
-    use auto_launch::AutoLaunchBuilder;
     use clap::Parser;
-    use std::env;
 
-    #[derive(Parser, Debug)]
-    #[command(version, about, long_about = None)]
-    struct Args {
-        /// Autostart mirai on boot
-        #[arg(long, action)]
-        autostart: Option<bool>,
+    let args = Args::parse();
+
+    #[cfg(target_os = "windows")]
+    if let Some(autostart) = args.autostart {
+        return set_autostart(autostart);
     }
 
-    let args = Args::parse();
+    let config = Config::create_config();
+    let _ = CONFIG.set(config);
+
+    let data_dir_path: PathBuf = get_config().data_dir.clone().into();
+    fs::create_dir_all(&data_dir_path).map_err(|err| err.to_string())?;
+
+    match args.command {
+        Some(command) => cli::run(command),
+        None => shared_main(),
+    }
+}
+
+// autostart is managed through the windows service that is registered
+#[cfg(target_os = "windows")]
+fn set_autostart(autostart: bool) -> Result<(), String> {
+    use auto_launch::AutoLaunchBuilder;
+    use std::env;
 
     let auto = AutoLaunchBuilder::new()
         .set_app_name("mirai")
@@ -58,36 +79,18 @@ fn main() -> Result<(), String> {
         .build()
         .unwrap();
 
-    if let Some(autostart) = args.autostart {
-        if autostart {
-            auto.enable().map_err(|err| err.to_string())?;
-            return Ok(());
-        } else {
-            auto.disable().map_err(|err| err.to_string())?;
-            return Ok(());
-        }
+    if autostart {
+        auto.enable().map_err(|err| err.to_string())
+    } else {
+        auto.disable().map_err(|err| err.to_string())
     }
-
-    shared_main()
-}
-
-// main function for linux, does not have CLI as cli is used
-// only to manage the windows service that is registered
-#[cfg(not(target_os = "windows"))]
-fn main() -> Result<(), String> {
-    shared_main()
 }
 
 fn shared_main() -> Result<(), String> {
-    let config = Config::create_config();
-    let _ = CONFIG.set(config);
-
     let log_level = get_config().file_config.log_level;
     let log_level = log_level.unwrap_or(LogLevel(Level::INFO));
 
     let data_dir_path: PathBuf = get_config().data_dir.clone().into();
-    fs::create_dir_all(&data_dir_path).map_err(|err| err.to_string())?;
-
     init_logging(log_level.inner(), &data_dir_path)?;
 
     tracing::info!("starting mirai");
@@ -96,38 +99,54 @@ fn shared_main() -> Result<(), String> {
     let data_dir_str = data_dir_path.to_str().unwrap_or("N/A");
     tracing::debug!("using data directory at {}", data_dir_str);
 
-    let backend = get_backend();
+    let backend = get_backend()?;
     let store = Store::new().map_err(|err| err.to_string())?;
     let content_manager = get_content_manager();
 
-    // wait for backend to be ready before continuning
-    let mut attempts = 0;
-    while !backend.is_ready() {
-        if attempts > MAX_BACKEND_READY_ATTEMPTS {
-            return Err("backend was never ready".to_string());
-        }
-        tracing::info!("waiting for backend to be ready");
-        sleep(Duration::from_secs(1));
-        attempts += 1;
+    if let Some(err) = store.get_last_error() {
+        tracing::warn!("last wallpaper change before this start failed: {}", err);
     }
 
+    // wait for backend to be ready before continuning
+    let retry_policy = RetryPolicy::from_config(&get_config().file_config.retry);
+    retry_policy
+        .wait_until_ready(&backend, MAX_BACKEND_READY_CHECKS)
+        .map_err(|_| "backend was never ready".to_string())?;
+
     let mut wallpaper_manager = WallpapersManager::new(&store, backend);
     wallpaper_manager
         .store_wallpapers(&content_manager)
         .map_err(|err| err.to_string())?;
 
     // if wallpaper needs changing, dont set current wallpaper, handle next change in loop
-    let last_update = store.get_last_update();
-    if !should_update_wallpaper(get_config().file_config.update_interval, last_update) {
-        wallpaper_manager.set_last_wallpaper();
-    }
+    let mut state = LoopState::new(!wallpaper_manager.should_update_wallpaper());
+
+    let health_check_interval =
+        Duration::from_secs(get_config().file_config.health_check_interval.max(1));
 
     loop {
-        let last_update = store.get_last_update();
-        if should_update_wallpaper(get_config().file_config.update_interval, last_update) {
-            wallpaper_manager.set_next_wallpaper(&content_manager);
+        wallpaper_manager.check(&mut state, &content_manager);
+
+        let slept = Duration::from_secs(get_seconds_till_minute()).min(health_check_interval);
+        let jumps = JumpDetector::new(store.clock().now(), Instant::now(), slept);
+        sleep(slept);
+
+        // after a suspend the schedule and the backend's state are both stale
+        match jumps.check(store.clock().now(), Instant::now()) {
+            Some(TimeJump::Forward(gap)) => {
+                tracing::info!(
+                    "clock moved forward by {}s, probably resumed from suspend",
+                    gap.num_seconds()
+                );
+                state.needs_restore = true;
+            }
+            Some(TimeJump::Backward(gap)) => {
+                tracing::info!("clock moved back by {}s", gap.num_seconds());
+                wallpaper_manager.resync_schedule();
+                state.needs_restore = true;
+            }
+            None => {}
         }
-        sleep(Duration::from_secs(get_seconds_till_minute()));
     }
 }
 
@@ -172,15 +191,40 @@ fn get_content_manager() -> ContentManager {
     }
 }
 
-#[cfg(target_os = "windows")]
-fn get_backend() -> impl WallpaperBackend {
-    use backends::windows::Windows;
-    Windows::new()
-}
+fn get_backend() -> Result<BackendChain, String> {
+    let mut backends = vec![];
+
+    for backend_type in get_config().file_config.backend.backends() {
+        let backend_type = match backend_type {
+            BackendTypes::Auto => {
+                let (backend_type, reason) = backends::detect_backend();
+                tracing::info!("auto-detected {} backend: {}", backend_type, reason);
+                backend_type
+            }
+            backend_type => {
+                tracing::info!("using {} backend from config", backend_type);
+                backend_type
+            }
+        };
+
+        if backends
+            .iter()
+            .any(|(existing, _)| *existing == backend_type)
+        {
+            continue;
+        }
 
-#[cfg(not(target_os = "windows"))]
-fn get_backend() -> impl WallpaperBackend {
-    SwwCliBackend::new()
+        match Backend::new(backend_type) {
+            Ok(backend) => backends.push((backend_type, backend)),
+            Err(err) => tracing::warn!("skipping {} backend: {}", backend_type, err),
+        }
+    }
+
+    if backends.is_empty() {
+        return Err("no usable backend configured".to_string());
+    }
+
+    Ok(BackendChain::new(backends))
 }
 
 fn get_seconds_till_minute() -> u64 {
@@ -191,33 +235,3 @@ fn get_seconds_till_minute() -> u64 {
     let seconds = now % 60;
     60 - seconds
 }
-
-fn should_update_wallpaper(interval: u32, last_run_time: Option<DateTime<Local>>) -> bool {
-    let current_time = Local::now();
-    let today = Local
-        .with_ymd_and_hms(
-            current_time.year(),
-            current_time.month(),
-            current_time.day(),
-            0,
-            0,
-            0,
-        )
-        .single()
-        .expect("failed to get start of day");
-
-    let total_mins_today = (current_time - today).num_minutes() as f64;
-    let group = (total_mins_today / interval as f64).floor() as u32;
-
-    let mut should_run = true;
-
-    if let Some(last_run) = last_run_time {
-        if last_run.date_naive() == current_time.date_naive() {
-            let last_group_mins = (last_run - today).num_minutes() as f64;
-            let last_group = (last_group_mins / interval as f64).floor() as u32;
-            should_run = group != last_group
-        }
-    }
-
-    should_run
-}
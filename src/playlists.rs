@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::get_config;
+use crate::store::Store;
+
+/// Playlist file at the root of a wallpaper source, in the same format as
+/// the `[playlists]` config table
+pub const PLAYLIST_FILE: &str = "playlists.toml";
+
+pub type Playlists = BTreeMap<String, Playlist>;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Playlist {
+    // wallpaper ids, in the order they are shown when sequential
+    pub wallpapers: Vec<String>,
+    #[serde(default)]
+    pub order: PlaylistOrder,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistOrder {
+    #[default]
+    Sequential,
+    // weighted random pick among the wallpapers not yet seen in the playlist
+    Random,
+}
+
+pub fn parse(contents: &str) -> Result<Playlists, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+/// Playlists from the config and from the source's playlist file, the config
+/// winning when both define the same name
+pub fn all(store: &Store) -> Playlists {
+    let mut playlists = match store
+        .get_source_playlists()
+        .map(|contents| parse(&contents))
+    {
+        Some(Ok(playlists)) => playlists,
+        Some(Err(err)) => {
+            tracing::warn!("ignoring {}: {}", PLAYLIST_FILE, err);
+            Playlists::new()
+        }
+        None => Playlists::new(),
+    };
+
+    playlists.extend(get_config().file_config.playlists.clone());
+    playlists
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let playlists = parse(
+            r#"
+            [work]
+            wallpapers = ["minimal/grey.png", "minimal/white.png"]
+
+            [weekend]
+            wallpapers = ["nature/forest.png", "space/orion.png"]
+            order = "random"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(playlists.len(), 2);
+        assert_eq!(
+            playlists["work"].wallpapers,
+            ["minimal/grey.png", "minimal/white.png"]
+        );
+        assert_eq!(playlists["work"].order, PlaylistOrder::Sequential);
+        assert_eq!(playlists["weekend"].order, PlaylistOrder::Random);
+
+        assert!(parse("[broken]\norder = \"random\"").is_err());
+    }
+}
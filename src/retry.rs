@@ -0,0 +1,168 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::backends::{WallpaperBackend, WallpaperBackendError};
+use crate::config::RetryConfig;
+
+/// Exponential backoff shared by startup and scheduled wallpaper changes
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub attempts: u32,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &RetryConfig) -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Duration::from_secs(config.initial_delay),
+            max_delay: Duration::from_secs(config.max_delay.max(config.initial_delay)),
+            multiplier: config.multiplier.max(1.0),
+            attempts: config.attempts.max(1),
+        }
+    }
+
+    /// Delay before the retry following `attempt`, starting from attempt 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        if self.initial_delay.is_zero() {
+            return Duration::ZERO;
+        }
+
+        // clamped in seconds first, the unclamped delay can outgrow a Duration
+        let factor = self
+            .multiplier
+            .powi(attempt.saturating_sub(1).min(i32::MAX as u32) as i32);
+        let seconds = (self.initial_delay.as_secs_f64() * factor).min(self.max_delay.as_secs_f64());
+        // only out of range when rounding a huge max delay up
+        Duration::try_from_secs_f64(seconds).unwrap_or(self.max_delay)
+    }
+
+    /// Blocks until the backend reports it is ready, backing off between
+    /// checks, and gives up after `checks` of them
+    pub fn wait_until_ready(
+        &self,
+        backend: &dyn WallpaperBackend,
+        checks: u32,
+    ) -> Result<(), WallpaperBackendError> {
+        let mut attempt = 0;
+        while !backend.is_ready() {
+            attempt += 1;
+            if attempt > checks {
+                return Err(WallpaperBackendError::NotReady);
+            }
+
+            let delay = self.delay(attempt);
+            tracing::info!(
+                "waiting for backend to be ready, checking again in {}s",
+                delay.as_secs()
+            );
+            sleep(delay);
+        }
+
+        Ok(())
+    }
+
+    /// Runs `op` until it succeeds, fails with a non retryable error or runs out of attempts
+    ///
+    /// Between attempts the backend has to report itself ready again before `op` is retried.
+    /// If it doesn't within as many checks as there are attempts, `NotReady` is returned so
+    /// the caller can get on with other work and try again later
+    pub fn retry<T>(
+        &self,
+        backend: &dyn WallpaperBackend,
+        mut op: impl FnMut() -> Result<T, WallpaperBackendError>,
+    ) -> Result<T, WallpaperBackendError> {
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Ok(value) => return Ok(value),
+                Err(err) if !err.is_retryable() || attempt >= self.attempts => return Err(err),
+                Err(err) => {
+                    let delay = self.delay(attempt);
+                    tracing::warn!(
+                        "attempt {}/{} failed: {}, retrying in {}s",
+                        attempt,
+                        self.attempts,
+                        err,
+                        delay.as_secs()
+                    );
+                    sleep(delay);
+                    self.wait_until_ready(backend, self.attempts)?;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backends::recording::RecordingBackend;
+
+    use super::*;
+
+    #[test]
+    fn test_delay_backs_off_up_to_max() {
+        let policy = RetryPolicy::from_config(&RetryConfig {
+            initial_delay: 5,
+            max_delay: 60,
+            multiplier: 2.0,
+            attempts: 5,
+        });
+
+        let delays = (1..=6)
+            .map(|attempt| policy.delay(attempt).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![5, 10, 20, 40, 60, 60]);
+    }
+
+    #[test]
+    fn test_delay_after_a_long_outage() {
+        let policy = RetryPolicy::from_config(&RetryConfig::default());
+
+        assert_eq!(policy.delay(63), Duration::from_secs(300));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(300));
+
+        let huge = RetryPolicy::from_config(&RetryConfig {
+            initial_delay: u64::MAX,
+            max_delay: u64::MAX,
+            ..RetryConfig::default()
+        });
+        assert_eq!(huge.delay(1), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn test_wait_until_ready_gives_up() {
+        let policy = RetryPolicy::from_config(&RetryConfig {
+            initial_delay: 0,
+            ..RetryConfig::default()
+        });
+        let backend = RecordingBackend::new();
+
+        assert!(policy.wait_until_ready(&backend, 3).is_ok());
+        backend.set_ready(false);
+        assert!(matches!(
+            policy.wait_until_ready(&backend, 3),
+            Err(WallpaperBackendError::NotReady)
+        ));
+    }
+
+    #[test]
+    fn test_retry_returns_when_backend_stays_down() {
+        let policy = RetryPolicy::from_config(&RetryConfig {
+            initial_delay: 0,
+            ..RetryConfig::default()
+        });
+        let backend = RecordingBackend::new();
+        backend.set_ready(false);
+
+        let mut calls = 0;
+        let result = policy.retry(&backend, || {
+            calls += 1;
+            Err::<(), _>(WallpaperBackendError::Rejected("busy".to_string()))
+        });
+        assert!(matches!(result, Err(WallpaperBackendError::NotReady)));
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,193 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Timelike};
+
+/// The schedule slot a time falls in: its local date and which `interval`
+/// minute window of that day it is in
+///
+/// Slots follow the wall clock rather than time elapsed since midnight, so a
+/// DST shift moves the schedule with the clock instead of skewing it by an
+/// hour, and a day without a midnight (or with two) needs no special casing
+pub fn slot<Tz: TimeZone>(interval: u32, time: &DateTime<Tz>) -> (NaiveDate, u32) {
+    let local = time.naive_local();
+    let minutes = local.time().num_seconds_from_midnight() / 60;
+    (local.date(), minutes / interval.max(1))
+}
+
+/// Whether the wallpaper is due to change, with changes happening every
+/// `interval` minutes counted from local midnight
+///
+/// The last run is compared in the timezone of `current_time`, so a change
+/// of system timezone is picked up on the next check
+pub fn should_update_wallpaper<Tz: TimeZone, LastTz: TimeZone>(
+    interval: u32,
+    last_run_time: Option<DateTime<LastTz>>,
+    current_time: DateTime<Tz>,
+) -> bool {
+    match last_run_time {
+        Some(last_run) => {
+            let last_run = last_run.with_timezone(&current_time.timezone());
+            slot(interval, &last_run) != slot(interval, &current_time)
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use chrono_tz::America::{Havana, New_York, Santiago};
+    use chrono_tz::Europe::London;
+    use chrono_tz::Tz;
+
+    use super::*;
+
+    fn at(tz: Tz, year: i32, month: u32, day: u32, hour: u32, min: u32) -> DateTime<Tz> {
+        tz.with_ymd_and_hms(year, month, day, hour, min, 0)
+            .earliest()
+            .unwrap()
+    }
+
+    fn london(year: i32, month: u32, day: u32, hour: u32, min: u32) -> DateTime<Tz> {
+        at(London, year, month, day, hour, min)
+    }
+
+    // steps through `hours` of real time a minute at a time, returning the
+    // wall clock times at which the wallpaper changed
+    fn changes(interval: u32, start: DateTime<Tz>, hours: i64) -> Vec<String> {
+        let mut last_run = start;
+        let mut now = start;
+        let mut changes = vec![];
+        for _ in 0..(hours * 60) {
+            now += TimeDelta::minutes(1);
+            if should_update_wallpaper(interval, Some(last_run), now) {
+                last_run = now;
+                changes.push(now.format("%H:%M %Z").to_string());
+            }
+        }
+        changes
+    }
+
+    #[test]
+    fn test_first_run_always_updates() {
+        assert!(should_update_wallpaper(
+            1440,
+            None::<DateTime<Tz>>,
+            london(2024, 6, 1, 12, 0)
+        ));
+    }
+
+    #[test]
+    fn test_updates_once_per_slot() {
+        let last_run = london(2024, 6, 1, 10, 0);
+        assert!(!should_update_wallpaper(
+            60,
+            Some(last_run),
+            london(2024, 6, 1, 10, 59)
+        ));
+        assert!(should_update_wallpaper(
+            60,
+            Some(last_run),
+            london(2024, 6, 1, 11, 0)
+        ));
+        assert!(!should_update_wallpaper(
+            30,
+            Some(london(2024, 6, 1, 11, 31)),
+            london(2024, 6, 1, 11, 59)
+        ));
+    }
+
+    #[test]
+    fn test_day_rollover() {
+        // daily changes happen at midnight
+        let last_run = london(2024, 6, 1, 23, 30);
+        assert!(!should_update_wallpaper(
+            1440,
+            Some(last_run),
+            london(2024, 6, 1, 23, 59)
+        ));
+        assert!(should_update_wallpaper(
+            1440,
+            Some(last_run),
+            london(2024, 6, 2, 0, 0)
+        ));
+
+        // the last slot of the day ends at midnight, not at the next interval
+        let last_run = london(2024, 6, 1, 23, 0);
+        assert!(should_update_wallpaper(
+            120,
+            Some(last_run),
+            london(2024, 6, 2, 0, 5)
+        ));
+    }
+
+    #[test]
+    fn test_hourly_changes_follow_wall_clock_across_dst() {
+        // clocks go forward from 01:00 to 02:00 on 2024-03-31 in London
+        assert_eq!(
+            changes(60, london(2024, 3, 31, 0, 0), 4),
+            vec!["02:00 BST", "03:00 BST", "04:00 BST", "05:00 BST"]
+        );
+
+        // and back from 02:00 to 01:00 on 2024-10-27, repeating the 01:00 slot
+        assert_eq!(
+            changes(60, london(2024, 10, 27, 0, 0), 4),
+            vec!["01:00 BST", "02:00 GMT", "03:00 GMT"]
+        );
+    }
+
+    #[test]
+    fn test_days_without_a_single_midnight() {
+        // midnight is skipped in Santiago on 2024-09-08, clocks jump to 01:00
+        let last_run = at(Santiago, 2024, 9, 7, 12, 0);
+        assert!(should_update_wallpaper(
+            1440,
+            Some(last_run),
+            at(Santiago, 2024, 9, 8, 1, 0)
+        ));
+        assert!(!should_update_wallpaper(
+            1440,
+            Some(at(Santiago, 2024, 9, 8, 1, 0)),
+            at(Santiago, 2024, 9, 8, 23, 59)
+        ));
+        assert_eq!(
+            changes(480, at(Santiago, 2024, 9, 7, 20, 0), 12),
+            vec!["01:00 -03", "08:00 -03"]
+        );
+
+        // midnight happens twice in Havana on 2024-11-03, clocks go back from 01:00
+        let first_midnight = at(Havana, 2024, 11, 3, 0, 0);
+        assert!(should_update_wallpaper(
+            1440,
+            Some(at(Havana, 2024, 11, 2, 12, 0)),
+            first_midnight
+        ));
+        assert!(!should_update_wallpaper(
+            1440,
+            Some(first_midnight),
+            first_midnight + TimeDelta::hours(1)
+        ));
+    }
+
+    #[test]
+    fn test_timezone_change_uses_current_timezone() {
+        // ran at 23:30 in London, which is 18:30 in New York
+        let last_run = london(2024, 6, 1, 23, 30);
+
+        // still the same day in New York, so no change
+        let now = at(New_York, 2024, 6, 1, 20, 0);
+        assert!(!should_update_wallpaper(1440, Some(last_run), now));
+
+        // but London has already passed midnight
+        assert!(should_update_wallpaper(
+            1440,
+            Some(last_run),
+            now.with_timezone(&London)
+        ));
+
+        // and New York gets its own midnight
+        assert!(should_update_wallpaper(
+            1440,
+            Some(last_run),
+            at(New_York, 2024, 6, 2, 0, 0)
+        ));
+    }
+}
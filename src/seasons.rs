@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{Datelike, Month, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::tags;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Hemisphere {
+    #[default]
+    Northern,
+    Southern,
+}
+
+/// Meteorological seasons, each three whole months
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Season {
+    Spring,
+    Summer,
+    #[serde(alias = "fall")]
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub fn of(date: NaiveDate, hemisphere: Hemisphere) -> Season {
+        let northern = match date.month() {
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            9..=11 => Season::Autumn,
+            _ => Season::Winter,
+        };
+
+        match hemisphere {
+            Hemisphere::Northern => northern,
+            Hemisphere::Southern => match northern {
+                Season::Spring => Season::Autumn,
+                Season::Summer => Season::Winter,
+                Season::Autumn => Season::Spring,
+                Season::Winter => Season::Summer,
+            },
+        }
+    }
+}
+
+/// Folders and tags to show in a season or month. Entries ending in `/` are
+/// folders, relative to the wallpapers folder or git path, anything else is
+/// a tag
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SeasonalCollections {
+    pub hemisphere: Hemisphere,
+    pub seasons: HashMap<Season, Vec<String>>,
+    // keyed by month name, e.g. "december" or "dec", checked when loading the config
+    #[serde(deserialize_with = "deserialize_months")]
+    pub months: BTreeMap<String, Vec<String>>,
+}
+
+impl SeasonalCollections {
+    /// The collection in season on `date`, a month's taking precedence over
+    /// its season's. `None` when nothing is mapped to either
+    pub fn in_season(&self, date: NaiveDate) -> Option<&[String]> {
+        let month = Month::try_from(date.month() as u8).ok()?;
+        let by_month = self
+            .months
+            .iter()
+            .find_map(|(name, entries)| (name.parse::<Month>().ok()? == month).then_some(entries));
+
+        by_month
+            .or_else(|| self.seasons.get(&Season::of(date, self.hemisphere)))
+            .map(Vec::as_slice)
+    }
+}
+
+fn deserialize_months<'de, D>(deserializer: D) -> Result<BTreeMap<String, Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let months = BTreeMap::<String, Vec<String>>::deserialize(deserializer)?;
+    if let Some(name) = months.keys().find(|name| name.parse::<Month>().is_err()) {
+        return Err(serde::de::Error::custom(format!(
+            "unknown month {} in [seasonal.months]",
+            name
+        )));
+    }
+
+    Ok(months)
+}
+
+/// Whether a wallpaper is in one of the folders, or has one of the tags, of a collection
+pub fn matches(collection: &[String], id: &str, tags: Option<&HashSet<String>>) -> bool {
+    collection.iter().any(|entry| {
+        if entry.ends_with('/') {
+            id.starts_with(entry.as_str())
+        } else {
+            tags.is_some_and(|tags| tags.contains(&tags::normalize(entry)))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_season_of() {
+        let north = Hemisphere::Northern;
+        assert_eq!(Season::of(date("2024-03-01"), north), Season::Spring);
+        assert_eq!(Season::of(date("2024-08-31"), north), Season::Summer);
+        assert_eq!(Season::of(date("2024-10-15"), north), Season::Autumn);
+        assert_eq!(Season::of(date("2024-01-15"), north), Season::Winter);
+        assert_eq!(Season::of(date("2024-12-01"), north), Season::Winter);
+
+        let south = Hemisphere::Southern;
+        assert_eq!(Season::of(date("2024-10-15"), south), Season::Spring);
+        assert_eq!(Season::of(date("2024-01-15"), south), Season::Summer);
+        assert_eq!(Season::of(date("2024-04-15"), south), Season::Autumn);
+        assert_eq!(Season::of(date("2024-07-15"), south), Season::Winter);
+    }
+
+    #[test]
+    fn test_in_season() {
+        let collections: SeasonalCollections = toml::from_str(
+            r#"
+            hemisphere = "southern"
+
+            [seasons]
+            fall = ["autumn", "leaves/"]
+            summer = ["beach"]
+
+            [months]
+            Dec = ["christmas"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            collections.in_season(date("2024-04-10")),
+            Some(["autumn".to_string(), "leaves/".to_string()].as_slice())
+        );
+        // december is summer down south, but the month is more specific
+        assert_eq!(
+            collections.in_season(date("2024-12-10")),
+            Some(["christmas".to_string()].as_slice())
+        );
+        assert_eq!(
+            collections.in_season(date("2024-01-10")),
+            Some(["beach".to_string()].as_slice())
+        );
+        assert_eq!(collections.in_season(date("2024-07-10")), None);
+
+        assert!(toml::from_str::<SeasonalCollections>("[months]\nsmarch = [\"ides\"]").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let collection = ["autumn".to_string(), "nature/leaves/".to_string()];
+        let tags = ["Autumn"].map(str::to_lowercase).into_iter().collect();
+
+        assert!(matches(&collection, "maple.png", Some(&tags)));
+        assert!(matches(&collection, "nature/leaves/red.png", None));
+        assert!(!matches(&collection, "nature/leavesless.png", None));
+        assert!(!matches(&collection, "beach.png", None));
+    }
+}
@@ -0,0 +1,433 @@
+use chrono::NaiveDate;
+use rand::Rng;
+use rand::distr::Distribution;
+use rand::distr::weighted::WeightedIndex;
+
+use crate::config::{SelectionConfig, SelectionStrategy};
+use crate::store::DatabaseWallpaper;
+
+/// Picks the next wallpaper out of the unseen ones. The sequential strategies
+/// take the first unseen wallpaper in their order, so the seen state doubles
+/// as the position in the rotation
+pub fn pick_next<R: Rng + ?Sized>(
+    unseen: &[DatabaseWallpaper],
+    config: &SelectionConfig,
+    cycle_seed: u64,
+    rng: &mut R,
+) -> Option<usize> {
+    if config.strategy == SelectionStrategy::Random {
+        return pick_weighted(unseen, config, rng);
+    }
+
+    let ordered = unseen
+        .iter()
+        .enumerate()
+        .map(|(index, wallpaper)| (sort_key(wallpaper, config.strategy, cycle_seed), index));
+    let next = if config.reverse {
+        ordered.max()
+    } else {
+        ordered.min()
+    };
+
+    next.map(|(_, index)| index)
+}
+
+/// Leaves out recently shown wallpapers, `recent` being newest first. When
+/// that would leave nothing the window shrinks, so the oldest of them are
+/// allowed again first
+pub fn exclude_recent(unseen: Vec<DatabaseWallpaper>, recent: &[String]) -> Vec<DatabaseWallpaper> {
+    for window in (1..=recent.len()).rev() {
+        let allowed = unseen
+            .iter()
+            .filter(|wallpaper| !recent[..window].contains(&wallpaper.id))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !allowed.is_empty() {
+            return allowed;
+        }
+    }
+
+    unseen
+}
+
+/// Index into the sorted wallpaper ids that every instance sharing `seed`
+/// picks for a schedule slot
+pub fn seeded_index(sorted_ids: &[String], seed: &str, slot: (NaiveDate, u32)) -> Option<usize> {
+    if sorted_ids.is_empty() {
+        return None;
+    }
+
+    let (date, slot) = slot;
+    let hash = stable_hash(stable_hash(0, seed), &format!("{date}/{slot}"));
+    Some((hash % sorted_ids.len() as u64) as usize)
+}
+
+// ties, and wallpapers missing the date a strategy wants, fall back to the id
+fn sort_key(
+    wallpaper: &DatabaseWallpaper,
+    strategy: SelectionStrategy,
+    cycle_seed: u64,
+) -> (Option<i64>, u64, String) {
+    let (date, hash) = match strategy {
+        SelectionStrategy::Random | SelectionStrategy::Name | SelectionStrategy::Seeded => {
+            (None, 0)
+        }
+        SelectionStrategy::Modified => (wallpaper.modified.or(wallpaper.committed), 0),
+        SelectionStrategy::CommitDate => (wallpaper.committed.or(wallpaper.modified), 0),
+        SelectionStrategy::Shuffle => (None, stable_hash(cycle_seed, &wallpaper.id)),
+    };
+
+    (date, hash, wallpaper.id.clone())
+}
+
+/// FNV-1a over the seed and the id, unlike std's hasher it is the same on
+/// every run and platform, so orders derived from it can be persisted
+pub fn stable_hash(seed: u64, id: &str) -> u64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    seed.to_le_bytes()
+        .iter()
+        .chain(id.as_bytes())
+        .fold(OFFSET, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+/// Weight learned from feedback, relative to an unrated wallpaper that is
+/// never skipped. Three stars is neutral, and the share of showings that
+/// weren't skipped is smoothed so a single skip doesn't bury a wallpaper
+pub fn feedback_score(wallpaper: &DatabaseWallpaper) -> f64 {
+    let rating = wallpaper
+        .rating
+        .map_or(1.0, |rating| f64::from(rating) / NEUTRAL_RATING);
+    let shows = f64::from(wallpaper.shows);
+    let kept = (shows - f64::from(wallpaper.skips.min(wallpaper.shows)) + 1.0) / (shows + 1.0);
+
+    rating * kept
+}
+
+const NEUTRAL_RATING: f64 = 3.0;
+
+/// Weight a wallpaper is picked with once its feedback score, the favorite
+/// multiplier and the configured bias are applied
+pub fn effective_weight(wallpaper: &DatabaseWallpaper, config: &SelectionConfig) -> f64 {
+    let weight = wallpaper.weight * feedback_score(wallpaper);
+    let weight = if wallpaper.favorite {
+        weight * config.favorite_weight
+    } else {
+        weight
+    };
+
+    weight.max(0.0).powf(config.weight_bias.max(0.0))
+}
+
+/// Whether a wallpaper can be picked at all, a weight of 0 never is unless
+/// the bias ignores weights
+pub fn is_pickable(wallpaper: &DatabaseWallpaper, config: &SelectionConfig) -> bool {
+    effective_weight(wallpaper, config) > 0.0
+}
+
+/// Picks an index into `candidates` at random, proportional to their
+/// weights. `None` when there are none or every weight is zero
+pub fn pick_weighted<R: Rng + ?Sized>(
+    candidates: &[DatabaseWallpaper],
+    config: &SelectionConfig,
+    rng: &mut R,
+) -> Option<usize> {
+    let weights = candidates
+        .iter()
+        .map(|wallpaper| effective_weight(wallpaper, config));
+    WeightedIndex::new(weights)
+        .ok()
+        .map(|index| index.sample(rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn wallpaper(id: &str, favorite: bool, weight: f64) -> DatabaseWallpaper {
+        DatabaseWallpaper {
+            id: id.to_string(),
+            seen: false,
+            manager_id: 0,
+            favorite,
+            weight,
+            banned: false,
+            modified: None,
+            committed: None,
+            rating: None,
+            shows: 0,
+            skips: 0,
+            hash: None,
+            in_source: true,
+        }
+    }
+
+    fn dated(id: &str, modified: Option<i64>, committed: Option<i64>) -> DatabaseWallpaper {
+        DatabaseWallpaper {
+            modified,
+            committed,
+            ..wallpaper(id, false, 1.0)
+        }
+    }
+
+    // the order a strategy walks through, by marking each pick as seen
+    fn walk(
+        mut unseen: Vec<DatabaseWallpaper>,
+        config: &SelectionConfig,
+        seed: u64,
+    ) -> Vec<String> {
+        let mut order = vec![];
+        while let Some(index) = pick_next(&unseen, config, seed, &mut rand::rng()) {
+            order.push(unseen.remove(index).id);
+        }
+        order
+    }
+
+    fn strategy(strategy: SelectionStrategy, reverse: bool) -> SelectionConfig {
+        SelectionConfig {
+            strategy,
+            reverse,
+            ..SelectionConfig::default()
+        }
+    }
+
+    // how often each candidate gets picked over a number of draws
+    fn picks(candidates: &[DatabaseWallpaper], config: &SelectionConfig) -> Vec<usize> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = vec![0; candidates.len()];
+        for _ in 0..10_000 {
+            counts[pick_weighted(candidates, config, &mut rng).unwrap()] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_effective_weight() {
+        let config = SelectionConfig::default();
+        assert_eq!(effective_weight(&wallpaper("a", false, 1.0), &config), 1.0);
+        assert_eq!(effective_weight(&wallpaper("a", true, 1.0), &config), 3.0);
+        assert_eq!(effective_weight(&wallpaper("a", true, 2.0), &config), 6.0);
+
+        let squared = SelectionConfig {
+            weight_bias: 2.0,
+            ..SelectionConfig::default()
+        };
+        assert_eq!(effective_weight(&wallpaper("a", true, 1.0), &squared), 9.0);
+
+        let uniform = SelectionConfig {
+            weight_bias: 0.0,
+            ..SelectionConfig::default()
+        };
+        assert_eq!(effective_weight(&wallpaper("a", true, 5.0), &uniform), 1.0);
+    }
+
+    #[test]
+    fn test_feedback_score() {
+        let rated = |rating, shows, skips| DatabaseWallpaper {
+            rating,
+            shows,
+            skips,
+            ..wallpaper("a", false, 1.0)
+        };
+
+        assert_eq!(feedback_score(&rated(None, 0, 0)), 1.0);
+        assert_eq!(feedback_score(&rated(Some(3), 10, 0)), 1.0);
+        assert_eq!(feedback_score(&rated(Some(5), 0, 0)), 5.0 / 3.0);
+        assert_eq!(feedback_score(&rated(Some(1), 0, 0)), 1.0 / 3.0);
+        assert_eq!(feedback_score(&rated(None, 1, 1)), 0.5);
+        assert_eq!(feedback_score(&rated(None, 9, 3)), 0.7);
+        // skipped every time, still never quite zero
+        assert!(feedback_score(&rated(None, 20, 20)) > 0.0);
+
+        let config = SelectionConfig::default();
+        assert_eq!(
+            effective_weight(
+                &DatabaseWallpaper {
+                    favorite: true,
+                    ..rated(Some(1), 1, 1)
+                },
+                &config
+            ),
+            0.5
+        );
+    }
+
+    #[test]
+    fn test_favorites_are_picked_more_often() {
+        let candidates = [wallpaper("normal", false, 1.0), wallpaper("fav", true, 1.0)];
+
+        let counts = picks(&candidates, &SelectionConfig::default());
+        let ratio = counts[1] as f64 / counts[0] as f64;
+        assert!((2.5..3.5).contains(&ratio), "favorite ratio was {ratio}");
+
+        let uniform = SelectionConfig {
+            weight_bias: 0.0,
+            ..SelectionConfig::default()
+        };
+        let counts = picks(&candidates, &uniform);
+        let ratio = counts[1] as f64 / counts[0] as f64;
+        assert!((0.9..1.1).contains(&ratio), "uniform ratio was {ratio}");
+    }
+
+    #[test]
+    fn test_zero_weights() {
+        let config = SelectionConfig::default();
+
+        // a zero weight is never picked while something else can be
+        let candidates = [wallpaper("never", false, 0.0), wallpaper("a", false, 1.0)];
+        assert_eq!(picks(&candidates, &config)[0], 0);
+
+        // nor when only zero weights are left
+        let candidates = [wallpaper("a", false, 0.0), wallpaper("b", false, 0.0)];
+        assert_eq!(pick_weighted(&candidates, &config, &mut rand::rng()), None);
+        assert!(!is_pickable(&candidates[0], &config));
+
+        // unless the bias ignores weights
+        let uniform = SelectionConfig {
+            weight_bias: 0.0,
+            ..SelectionConfig::default()
+        };
+        assert!(is_pickable(&candidates[0], &uniform));
+
+        assert_eq!(pick_weighted(&[], &config, &mut rand::rng()), None);
+    }
+
+    #[test]
+    fn test_sequential_strategies() {
+        let wallpapers = vec![
+            dated("b.png", Some(300), Some(100)),
+            dated("c.png", Some(100), None),
+            dated("a.png", Some(200), Some(300)),
+        ];
+
+        assert_eq!(
+            walk(
+                wallpapers.clone(),
+                &strategy(SelectionStrategy::Name, false),
+                0
+            ),
+            ["a.png", "b.png", "c.png"]
+        );
+        assert_eq!(
+            walk(
+                wallpapers.clone(),
+                &strategy(SelectionStrategy::Name, true),
+                0
+            ),
+            ["c.png", "b.png", "a.png"]
+        );
+        assert_eq!(
+            walk(
+                wallpapers.clone(),
+                &strategy(SelectionStrategy::Modified, false),
+                0
+            ),
+            ["c.png", "a.png", "b.png"]
+        );
+        // c.png has no commit date, so its modification time is used
+        assert_eq!(
+            walk(
+                wallpapers.clone(),
+                &strategy(SelectionStrategy::CommitDate, false),
+                0
+            ),
+            ["b.png", "c.png", "a.png"]
+        );
+        assert_eq!(
+            walk(
+                wallpapers,
+                &strategy(SelectionStrategy::CommitDate, true),
+                0
+            ),
+            ["a.png", "c.png", "b.png"]
+        );
+    }
+
+    #[test]
+    fn test_shuffle_is_stable_per_seed() {
+        let wallpapers = (0..20)
+            .map(|idx| wallpaper(&format!("{idx}.png"), false, 1.0))
+            .collect::<Vec<_>>();
+        let config = strategy(SelectionStrategy::Shuffle, false);
+
+        let first = walk(wallpapers.clone(), &config, 1);
+        assert_eq!(first, walk(wallpapers.clone(), &config, 1));
+        assert_ne!(first, walk(wallpapers.clone(), &config, 2));
+
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_ne!(first, sorted);
+
+        // picking up halfway through continues the same order
+        let remaining = wallpapers
+            .into_iter()
+            .filter(|wallpaper| first[10..].contains(&wallpaper.id))
+            .collect();
+        assert_eq!(walk(remaining, &config, 1), first[10..]);
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        // fixed values, the shuffle order must not change between releases
+        assert_eq!(stable_hash(0, ""), 0xa8c7f832281a39c5);
+        assert_ne!(stable_hash(0, "a.png"), stable_hash(1, "a.png"));
+        assert_ne!(stable_hash(0, "a.png"), stable_hash(0, "b.png"));
+    }
+
+    #[test]
+    fn test_exclude_recent() {
+        let unseen = vec![
+            wallpaper("a", false, 1.0),
+            wallpaper("b", false, 1.0),
+            wallpaper("c", false, 1.0),
+        ];
+        let ids = |wallpapers: Vec<DatabaseWallpaper>| {
+            wallpapers
+                .into_iter()
+                .map(|wallpaper| wallpaper.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(exclude_recent(unseen.clone(), &[])), ["a", "b", "c"]);
+        assert_eq!(
+            ids(exclude_recent(unseen.clone(), &["c".to_string()])),
+            ["a", "b"]
+        );
+
+        // everything was shown recently, the oldest ones are let back in
+        let recent = ["c", "b", "a"].map(String::from);
+        assert_eq!(ids(exclude_recent(unseen, &recent)), ["a"]);
+    }
+
+    #[test]
+    fn test_seeded_index() {
+        let ids = ["a.png", "b.png", "c.png", "d.png", "e.png"].map(String::from);
+        let day = |day| (NaiveDate::from_ymd_opt(2024, 6, day).unwrap(), 0);
+
+        // the same seed and slot always give the same pick
+        assert_eq!(
+            seeded_index(&ids, "desk", day(1)),
+            seeded_index(&ids, "desk", day(1))
+        );
+
+        // fixed values, instances on different releases must agree
+        let picks = (1..=10)
+            .map(|idx| seeded_index(&ids, "desk", day(idx)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(picks, [0, 2, 2, 4, 4, 2, 4, 4, 1, 4]);
+
+        // another seed gives another sequence
+        let other = (1..=10)
+            .map(|idx| seeded_index(&ids, "other", day(idx)).unwrap())
+            .collect::<Vec<_>>();
+        assert_ne!(picks, other);
+
+        assert_eq!(seeded_index(&[], "desk", day(1)), None);
+    }
+}
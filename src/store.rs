@@ -1,13 +1,18 @@
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, TimeDelta};
 use rusqlite::{Connection, Error, Result, Row};
-use sea_query::{Expr, Iden, OnConflict, Query, SqliteQueryBuilder};
-use sea_query_rusqlite::RusqliteBinder;
+use sea_query::{Expr, Iden, OnConflict, Order, Query, SimpleExpr, SqliteQueryBuilder};
+use sea_query_rusqlite::{RusqliteBinder, RusqliteValues};
 use thiserror::Error;
 
+use crate::clock::{Clock, SystemClock};
 use crate::content_managers::ContentManagerTypes;
 use crate::get_config;
+use crate::tags::{Tag, TagSource};
 use crate::wallpaper::Wallpaper;
 
 #[derive(Debug, Error)]
@@ -16,12 +21,18 @@ pub enum StoreError {
     InsertFailed,
     #[error("failed to update row in store")]
     UpdateFailed,
+    #[error("no wallpaper with id {0} in store")]
+    UnknownWallpaper(String),
 }
 
 refinery::embed_migrations!("migrations");
 
+// weight of a wallpaper nobody has set one for
+const DEFAULT_WEIGHT: f64 = 1.0;
+
 pub struct Store {
     connection: Connection,
+    clock: Rc<dyn Clock>,
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +41,19 @@ pub struct DatabaseWallpaper {
     pub id: String,
     pub seen: bool,
     pub manager_id: u8,
+    pub favorite: bool,
+    pub weight: f64,
+    pub banned: bool,
+    pub modified: Option<i64>,
+    pub committed: Option<i64>,
+    // 1 to 5 stars, if rated
+    pub rating: Option<u8>,
+    pub shows: u32,
+    // times it was skipped soon after being shown
+    pub skips: u32,
+    pub hash: Option<String>,
+    // found by the last scan, the store keeps wallpapers removed from the source
+    pub in_source: bool,
 }
 
 impl From<&Row<'_>> for DatabaseWallpaper {
@@ -38,6 +62,16 @@ impl From<&Row<'_>> for DatabaseWallpaper {
             id: row.get_unwrap("id"),
             seen: row.get_unwrap("seen"),
             manager_id: row.get_unwrap("manager_id"),
+            favorite: row.get_unwrap("favorite"),
+            weight: row.get_unwrap("weight"),
+            banned: row.get_unwrap("banned"),
+            modified: row.get_unwrap("modified"),
+            committed: row.get_unwrap("committed"),
+            rating: row.get_unwrap("rating"),
+            shows: row.get_unwrap("shows"),
+            skips: row.get_unwrap("skips"),
+            hash: row.get_unwrap("hash"),
+            in_source: row.get_unwrap("in_source"),
         }
     }
 }
@@ -47,24 +81,86 @@ impl TryInto<Wallpaper> for DatabaseWallpaper {
 
     fn try_into(self) -> Result<Wallpaper, Self::Error> {
         let manager_id: ContentManagerTypes = self.manager_id.try_into()?;
-        Ok(Wallpaper::new(self.id, manager_id))
+        Ok(Wallpaper {
+            modified: self.modified,
+            committed: self.committed,
+            hash: self.hash,
+            ..Wallpaper::new(self.id, manager_id)
+        })
     }
 }
 
-#[derive(Iden)]
+#[derive(Iden, Clone, Copy)]
 enum SeenWallpapers {
     Table,
     Id,
     Seen,
     ManagerId,
+    Favorite,
+    Weight,
+    Banned,
+    Modified,
+    Committed,
+    Rating,
+    Shows,
+    Skips,
+    Hash,
+    InSource,
+}
+
+const WALLPAPER_COLUMNS: [SeenWallpapers; 13] = [
+    SeenWallpapers::Id,
+    SeenWallpapers::ManagerId,
+    SeenWallpapers::Seen,
+    SeenWallpapers::Favorite,
+    SeenWallpapers::Weight,
+    SeenWallpapers::Banned,
+    SeenWallpapers::Modified,
+    SeenWallpapers::Committed,
+    SeenWallpapers::Rating,
+    SeenWallpapers::Shows,
+    SeenWallpapers::Skips,
+    SeenWallpapers::Hash,
+    SeenWallpapers::InSource,
+];
+
+#[derive(Iden)]
+enum Tags {
+    Table,
+    WallpaperId,
+    Tag,
+    Source,
 }
 
 #[derive(Iden)]
+enum PlaylistSeen {
+    Table,
+    Playlist,
+    WallpaperId,
+}
+
+#[derive(Iden)]
+enum History {
+    Table,
+    Id,
+    WallpaperId,
+    ShownAt,
+    Skipped,
+}
+
+#[derive(Iden, Clone, Copy)]
 enum Meta {
     Table,
     Id,
     LastUpdate,
     LastUsed,
+    LastError,
+    Paused,
+    PausedUntil,
+    CycleSeed,
+    ActivePlaylist,
+    SourcePlaylists,
+    SourceCalendar,
 }
 
 fn log_query_error(err: &Error) {
@@ -77,6 +173,10 @@ fn log_query_error(err: &Error) {
 
 impl Store {
     pub fn new() -> Result<Store> {
+        Store::with_clock(Rc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Rc<dyn Clock>) -> Result<Store> {
         let mut conn = if cfg!(test) {
             Connection::open_in_memory()?
         } else {
@@ -84,11 +184,21 @@ impl Store {
             Connection::open(data_dir_path.join("data.sqlite"))?
         };
 
+        // the cli writes to the same database while the daemon is running
+        conn.busy_timeout(Duration::from_secs(5))?;
+
         migrations::runner().run(&mut conn).unwrap();
-        let store = Store { connection: conn };
+        let store = Store {
+            connection: conn,
+            clock,
+        };
         Ok(store)
     }
 
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
     pub fn mark_as_seen(&self, wallpaper: &Wallpaper) -> Result<(), StoreError> {
         tracing::info!("marking id as seen: {}", wallpaper.id);
         let manager_id: u8 = wallpaper.type_id.into();
@@ -147,12 +257,30 @@ impl Store {
                 SeenWallpapers::Id,
                 SeenWallpapers::Seen,
                 SeenWallpapers::ManagerId,
+                SeenWallpapers::Modified,
+                SeenWallpapers::Committed,
+                SeenWallpapers::Hash,
+                SeenWallpapers::InSource,
+            ])
+            .values_panic([
+                (&wallpaper.id).into(),
+                0.into(),
+                manager_id.into(),
+                wallpaper.modified.into(),
+                wallpaper.committed.into(),
+                wallpaper.hash.clone().into(),
+                true.into(),
             ])
-            .values_panic([(&wallpaper.id).into(), 0.into(), manager_id.into()])
             .on_conflict(
                 OnConflict::column(SeenWallpapers::Id)
                     // TODO: make primary key for this table a composite key with id:manager_id
-                    .update_column(SeenWallpapers::ManagerId)
+                    .update_columns([
+                        SeenWallpapers::ManagerId,
+                        SeenWallpapers::Modified,
+                        SeenWallpapers::Committed,
+                        SeenWallpapers::Hash,
+                        SeenWallpapers::InSource,
+                    ])
                     .to_owned(),
             )
             .build_rusqlite(SqliteQueryBuilder);
@@ -165,15 +293,10 @@ impl Store {
         Ok(())
     }
 
-    #[allow(dead_code)]
     pub fn get_inserted_wallpapers(&self) -> Vec<DatabaseWallpaper> {
         let (sql, _) = Query::select()
             .from(SeenWallpapers::Table)
-            .columns([
-                SeenWallpapers::Id,
-                SeenWallpapers::ManagerId,
-                SeenWallpapers::Seen,
-            ])
+            .columns(WALLPAPER_COLUMNS)
             .build_rusqlite(SqliteQueryBuilder);
         let stmt = self.connection.prepare(sql.as_str()).inspect_err(|err| {
             tracing::error!("error preparing query: {}", err);
@@ -198,14 +321,220 @@ impl Store {
     }
 
     pub fn get_unseen_wallpaperrs(&self) -> Vec<DatabaseWallpaper> {
+        self.select_wallpapers(
+            Expr::column(SeenWallpapers::Seen)
+                .eq(0)
+                .and(Expr::column(SeenWallpapers::Banned).eq(0))
+                .and(Expr::column(SeenWallpapers::InSource).eq(1)),
+        )
+    }
+
+    /// Wallpapers found by the last scan of the source, sorted by id. The
+    /// store also keeps those that have since been removed from it
+    pub fn get_source_wallpapers(&self) -> Vec<DatabaseWallpaper> {
+        let mut wallpapers = self.select_wallpapers(Expr::col(SeenWallpapers::InSource).eq(1));
+        wallpapers.sort_by(|a, b| a.id.cmp(&b.id));
+        wallpapers
+    }
+
+    /// Marks every wallpaper as removed from the source, ahead of a scan
+    /// inserting the ones still in it again
+    pub fn clear_source_wallpapers(&self) -> Result<(), StoreError> {
+        self.execute(
+            Query::update()
+                .table(SeenWallpapers::Table)
+                .value(SeenWallpapers::InSource, false)
+                .build_rusqlite(SqliteQueryBuilder),
+        )?;
+
+        Ok(())
+    }
+
+    /// Every stored wallpaper with this content, whichever source it came from
+    pub fn get_wallpapers_by_hash(&self, hash: &str) -> Vec<DatabaseWallpaper> {
+        self.select_wallpapers(Expr::col(SeenWallpapers::Hash).eq(hash))
+    }
+
+    /// Moves the state of a renamed or duplicate wallpaper over to `into`.
+    /// If `into` isn't stored yet `from` is renamed to it, otherwise their
+    /// state is combined and `from` is removed
+    pub fn merge_wallpaper(&self, from: &str, into: &str) -> Result<(), StoreError> {
+        let Some(source) = self
+            .select_wallpapers(Expr::col(SeenWallpapers::Id).eq(from))
+            .pop()
+        else {
+            return Ok(());
+        };
+        let target = self
+            .select_wallpapers(Expr::col(SeenWallpapers::Id).eq(into))
+            .pop();
+
+        self.transaction(|| {
+            match target {
+                None => self.execute(
+                    Query::update()
+                        .table(SeenWallpapers::Table)
+                        .value(SeenWallpapers::Id, into)
+                        .and_where(Expr::col(SeenWallpapers::Id).eq(from))
+                        .build_rusqlite(SqliteQueryBuilder),
+                )?,
+                Some(target) => {
+                    self.execute(
+                        Query::update()
+                            .table(SeenWallpapers::Table)
+                            .values([
+                                (SeenWallpapers::Seen, (target.seen || source.seen).into()),
+                                (
+                                    SeenWallpapers::Favorite,
+                                    (target.favorite || source.favorite).into(),
+                                ),
+                                (
+                                    SeenWallpapers::Weight,
+                                    if target.weight != DEFAULT_WEIGHT {
+                                        target.weight
+                                    } else {
+                                        source.weight
+                                    }
+                                    .into(),
+                                ),
+                                (
+                                    SeenWallpapers::Banned,
+                                    (target.banned || source.banned).into(),
+                                ),
+                                (
+                                    SeenWallpapers::Rating,
+                                    target.rating.or(source.rating).into(),
+                                ),
+                                (SeenWallpapers::Shows, (target.shows + source.shows).into()),
+                                (SeenWallpapers::Skips, (target.skips + source.skips).into()),
+                            ])
+                            .and_where(Expr::col(SeenWallpapers::Id).eq(into))
+                            .build_rusqlite(SqliteQueryBuilder),
+                    )?;
+                    self.execute(
+                        Query::delete()
+                            .from_table(SeenWallpapers::Table)
+                            .and_where(Expr::col(SeenWallpapers::Id).eq(from))
+                            .build_rusqlite(SqliteQueryBuilder),
+                    )?
+                }
+            };
+
+            self.execute(
+                Query::update()
+                    .table(History::Table)
+                    .value(History::WallpaperId, into)
+                    .and_where(Expr::col(History::WallpaperId).eq(from))
+                    .build_rusqlite(SqliteQueryBuilder),
+            )?;
+            self.execute(
+                Query::update()
+                    .table(Meta::Table)
+                    .value(Meta::LastUsed, into)
+                    .and_where(Expr::col(Meta::LastUsed).eq(from))
+                    .build_rusqlite(SqliteQueryBuilder),
+            )?;
+
+            // tags and playlist progress are copied over, skipping any `into` already has
+            self.execute(
+                Query::insert()
+                    .into_table(Tags::Table)
+                    .columns([Tags::WallpaperId, Tags::Tag, Tags::Source])
+                    .select_from(
+                        Query::select()
+                            .expr(Expr::val(into))
+                            .columns([Tags::Tag, Tags::Source])
+                            .from(Tags::Table)
+                            .and_where(Expr::col(Tags::WallpaperId).eq(from))
+                            .to_owned(),
+                    )
+                    .map_err(|_| StoreError::UpdateFailed)?
+                    .on_conflict(
+                        OnConflict::columns([Tags::WallpaperId, Tags::Tag, Tags::Source])
+                            .do_nothing()
+                            .to_owned(),
+                    )
+                    .build_rusqlite(SqliteQueryBuilder),
+            )?;
+            self.execute(
+                Query::delete()
+                    .from_table(Tags::Table)
+                    .and_where(Expr::col(Tags::WallpaperId).eq(from))
+                    .build_rusqlite(SqliteQueryBuilder),
+            )?;
+            self.execute(
+                Query::insert()
+                    .into_table(PlaylistSeen::Table)
+                    .columns([PlaylistSeen::Playlist, PlaylistSeen::WallpaperId])
+                    .select_from(
+                        Query::select()
+                            .column(PlaylistSeen::Playlist)
+                            .expr(Expr::val(into))
+                            .from(PlaylistSeen::Table)
+                            .and_where(Expr::col(PlaylistSeen::WallpaperId).eq(from))
+                            .to_owned(),
+                    )
+                    .map_err(|_| StoreError::UpdateFailed)?
+                    .on_conflict(
+                        OnConflict::columns([PlaylistSeen::Playlist, PlaylistSeen::WallpaperId])
+                            .do_nothing()
+                            .to_owned(),
+                    )
+                    .build_rusqlite(SqliteQueryBuilder),
+            )?;
+            self.execute(
+                Query::delete()
+                    .from_table(PlaylistSeen::Table)
+                    .and_where(Expr::col(PlaylistSeen::WallpaperId).eq(from))
+                    .build_rusqlite(SqliteQueryBuilder),
+            )?;
+
+            Ok(())
+        })
+    }
+
+    /// Runs `f` in a transaction, rolled back if it fails. Transactions can
+    /// be nested, an inner one only being kept when the outer one is
+    pub fn transaction<T>(
+        &self,
+        f: impl FnOnce() -> Result<T, StoreError>,
+    ) -> Result<T, StoreError> {
+        let savepoint = |sql: &str| {
+            self.connection
+                .execute_batch(sql)
+                .inspect_err(log_query_error)
+                .map_err(|_| StoreError::UpdateFailed)
+        };
+
+        savepoint("SAVEPOINT store")?;
+        match f() {
+            Ok(value) => {
+                savepoint("RELEASE store")?;
+                Ok(value)
+            }
+            Err(err) => {
+                let _ = savepoint("ROLLBACK TO store; RELEASE store");
+                Err(err)
+            }
+        }
+    }
+
+    fn execute(&self, (sql, values): (String, RusqliteValues)) -> Result<usize, StoreError> {
+        self.connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error)
+            .map_err(|_| StoreError::UpdateFailed)
+    }
+
+    pub fn get_banned_wallpapers(&self) -> Vec<DatabaseWallpaper> {
+        self.select_wallpapers(Expr::column(SeenWallpapers::Banned).eq(1))
+    }
+
+    fn select_wallpapers(&self, condition: SimpleExpr) -> Vec<DatabaseWallpaper> {
         let (sql, values) = Query::select()
             .from(SeenWallpapers::Table)
-            .columns([
-                SeenWallpapers::Id,
-                SeenWallpapers::ManagerId,
-                SeenWallpapers::Seen,
-            ])
-            .and_where(Expr::column(SeenWallpapers::Seen).eq(0))
+            .columns(WALLPAPER_COLUMNS)
+            .and_where(condition)
             .build_rusqlite(SqliteQueryBuilder);
         let stmt = self.connection.prepare(sql.as_str()).inspect_err(|err| {
             tracing::error!("error preparing query: {}", err);
@@ -229,10 +558,54 @@ impl Store {
         vec![]
     }
 
+    pub fn set_favorite(&self, id: &str, favorite: bool) -> Result<(), StoreError> {
+        self.update_wallpaper(id, SeenWallpapers::Favorite, favorite.into())
+    }
+
+    /// Bans keep the row around, so they outlive the file being removed and re-added
+    pub fn set_banned(&self, id: &str, banned: bool) -> Result<(), StoreError> {
+        self.update_wallpaper(id, SeenWallpapers::Banned, banned.into())
+    }
+
+    /// 1 to 5 stars, `None` clears the rating
+    pub fn set_rating(&self, id: &str, rating: Option<u8>) -> Result<(), StoreError> {
+        self.update_wallpaper(id, SeenWallpapers::Rating, rating.into())
+    }
+
+    pub fn set_weight(&self, id: &str, weight: f64) -> Result<(), StoreError> {
+        self.update_wallpaper(id, SeenWallpapers::Weight, weight.into())
+    }
+
+    fn update_wallpaper(
+        &self,
+        id: &str,
+        column: SeenWallpapers,
+        value: sea_query::Value,
+    ) -> Result<(), StoreError> {
+        let (sql, values) = Query::update()
+            .table(SeenWallpapers::Table)
+            .value(column, value)
+            .and_where(Expr::col(SeenWallpapers::Id).eq(id))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let updated = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error)
+            .map_err(|_| StoreError::UpdateFailed)?;
+
+        if updated == 0 {
+            return Err(StoreError::UnknownWallpaper(id.to_string()));
+        }
+
+        Ok(())
+    }
+
     pub fn reset_seen_state(&self) {
         let (sql, values) = Query::update()
             .table(SeenWallpapers::Table)
             .value(SeenWallpapers::Seen, 0)
+            .and_where(Expr::col(SeenWallpapers::Banned).eq(0))
             .build_rusqlite(SqliteQueryBuilder);
         self.connection
             .execute(sql.as_str(), &*values.as_params())
@@ -252,13 +625,14 @@ impl Store {
     }
 
     pub fn update_last_run(&self) {
-        let now = Local::now();
+        let now = self.clock.now();
         tracing::info!("updating last run to {}", now);
 
         let (sql, values) = Query::insert()
             .into_table(Meta::Table)
             .columns([Meta::Id, Meta::LastUpdate])
-            .values_panic([1.into(), now.naive_local().into()])
+            // keep the offset, naive times are read back as UTC
+            .values_panic([1.into(), now.fixed_offset().into()])
             .on_conflict(
                 OnConflict::column(Meta::Id)
                     .update_column(Meta::LastUpdate)
@@ -272,6 +646,219 @@ impl Store {
             .inspect_err(log_query_error);
     }
 
+    /// Replaces the tags found while scanning, manual tags are left alone
+    pub fn set_scanned_tags(&self, id: &str, tags: &[Tag]) -> Result<(), StoreError> {
+        let (sql, values) = Query::delete()
+            .from_table(Tags::Table)
+            .and_where(Expr::col(Tags::WallpaperId).eq(id))
+            .and_where(Expr::col(Tags::Source).ne(TagSource::Manual.as_str()))
+            .build_rusqlite(SqliteQueryBuilder);
+        self.connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error)
+            .map_err(|_| StoreError::UpdateFailed)?;
+
+        for tag in tags {
+            self.insert_tag(id, tag)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn add_tag(&self, id: &str, name: &str) -> Result<(), StoreError> {
+        if self.get_wallpaper(id).is_none() {
+            return Err(StoreError::UnknownWallpaper(id.to_string()));
+        }
+
+        self.insert_tag(id, &Tag::new(name, TagSource::Manual))
+    }
+
+    /// Removes a manual tag, returning whether there was one
+    pub fn remove_tag(&self, id: &str, name: &str) -> Result<bool, StoreError> {
+        let (sql, values) = Query::delete()
+            .from_table(Tags::Table)
+            .and_where(Expr::col(Tags::WallpaperId).eq(id))
+            .and_where(Expr::col(Tags::Tag).eq(Tag::new(name, TagSource::Manual).name))
+            .and_where(Expr::col(Tags::Source).eq(TagSource::Manual.as_str()))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let removed = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error)
+            .map_err(|_| StoreError::UpdateFailed)?;
+
+        Ok(removed > 0)
+    }
+
+    fn insert_tag(&self, id: &str, tag: &Tag) -> Result<(), StoreError> {
+        let (sql, values) = Query::insert()
+            .into_table(Tags::Table)
+            .columns([Tags::WallpaperId, Tags::Tag, Tags::Source])
+            .values_panic([id.into(), (&tag.name).into(), tag.source.as_str().into()])
+            .on_conflict(
+                OnConflict::columns([Tags::WallpaperId, Tags::Tag, Tags::Source])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .build_rusqlite(SqliteQueryBuilder);
+
+        self.connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error)
+            .map_err(|_| StoreError::InsertFailed)?;
+
+        Ok(())
+    }
+
+    pub fn get_tags(&self, id: &str) -> Vec<Tag> {
+        self.select_tags(Some(id))
+            .into_iter()
+            .map(|(_, tag)| tag)
+            .collect()
+    }
+
+    /// Tag names of every wallpaper, by wallpaper id
+    pub fn get_all_tags(&self) -> HashMap<String, HashSet<String>> {
+        let mut tags: HashMap<String, HashSet<String>> = HashMap::new();
+        for (id, tag) in self.select_tags(None) {
+            tags.entry(id).or_default().insert(tag.name);
+        }
+        tags
+    }
+
+    fn select_tags(&self, id: Option<&str>) -> Vec<(String, Tag)> {
+        let mut query = Query::select();
+        query
+            .from(Tags::Table)
+            .columns([Tags::WallpaperId, Tags::Tag, Tags::Source])
+            .order_by(Tags::Tag, Order::Asc);
+        if let Some(id) = id {
+            query.and_where(Expr::col(Tags::WallpaperId).eq(id));
+        }
+        let (sql, values) = query.build_rusqlite(SqliteQueryBuilder);
+
+        let Ok(mut stmt) = self.connection.prepare(sql.as_str()) else {
+            return vec![];
+        };
+        stmt.query_map(&*values.as_params(), |row| {
+            let source: String = row.get(2)?;
+            Ok((row.get(0)?, row.get(1)?, source))
+        })
+        .inspect_err(log_query_error)
+        .map(|rows| {
+            rows.filter_map(Result::ok)
+                .filter_map(|(id, name, source): (String, String, String)| {
+                    let source = TagSource::try_from(source.as_str()).ok()?;
+                    Some((id, Tag { name, source }))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    pub fn add_history(&self, wallpaper: &Wallpaper) {
+        let (sql, values) = Query::insert()
+            .into_table(History::Table)
+            .columns([History::WallpaperId, History::ShownAt])
+            .values_panic([
+                (&wallpaper.id).into(),
+                self.clock.now().fixed_offset().into(),
+            ])
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let _ = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error);
+        self.increment(&wallpaper.id, SeenWallpapers::Shows);
+    }
+
+    /// Counts a skip against the wallpaper shown last, if it was shown less
+    /// than `within` ago and this showing wasn't skipped already. Returns
+    /// the id of the skipped wallpaper
+    pub fn record_skip(&self, within: TimeDelta) -> Option<String> {
+        let (sql, values) = Query::select()
+            .from(History::Table)
+            .columns([
+                History::Id,
+                History::WallpaperId,
+                History::ShownAt,
+                History::Skipped,
+            ])
+            .order_by(History::Id, Order::Desc)
+            .limit(1)
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let (history_id, wallpaper_id, shown_at, skipped): (i64, String, DateTime<Local>, bool) =
+            self.connection
+                .query_row(sql.as_str(), &*values.as_params(), |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                })
+                .ok()?;
+        if skipped || self.clock.now() - shown_at >= within {
+            return None;
+        }
+
+        let (sql, values) = Query::update()
+            .table(History::Table)
+            .value(History::Skipped, true)
+            .and_where(Expr::col(History::Id).eq(history_id))
+            .build_rusqlite(SqliteQueryBuilder);
+        self.connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error)
+            .ok()?;
+        self.increment(&wallpaper_id, SeenWallpapers::Skips);
+
+        Some(wallpaper_id)
+    }
+
+    fn increment(&self, id: &str, column: SeenWallpapers) {
+        let (sql, values) = Query::update()
+            .table(SeenWallpapers::Table)
+            .value(column, Expr::col(column).add(1))
+            .and_where(Expr::col(SeenWallpapers::Id).eq(id))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let _ = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error);
+    }
+
+    /// Ids of the last `count` wallpapers shown, newest first
+    pub fn get_recent_history(&self, count: u32) -> Vec<String> {
+        let (sql, values) = Query::select()
+            .from(History::Table)
+            .column(History::WallpaperId)
+            .order_by(History::Id, Order::Desc)
+            .limit(count.into())
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let Ok(mut stmt) = self.connection.prepare(sql.as_str()) else {
+            return vec![];
+        };
+        stmt.query_map(&*values.as_params(), |row| row.get(0))
+            .inspect_err(log_query_error)
+            .map(|rows| rows.filter_map(Result::ok).collect())
+            .unwrap_or_default()
+    }
+
+    /// Forgets when the wallpaper last changed, so the next check changes it
+    pub fn clear_last_update(&self) {
+        let (sql, values) = Query::update()
+            .table(Meta::Table)
+            .value(Meta::LastUpdate, Option::<String>::None)
+            .and_where(Expr::col(Meta::Id).eq(1))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let _ = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error);
+    }
+
     pub fn set_last_used(&self, wallpaper: &Wallpaper) {
         tracing::info!("updating last used wallpaper to {}", &wallpaper.id);
 
@@ -292,6 +879,227 @@ impl Store {
             .inspect_err(log_query_error);
     }
 
+    /// Records why the last wallpaper change failed, or clears it when `None`
+    pub fn set_last_error(&self, error: Option<&str>) {
+        let (sql, values) = Query::insert()
+            .into_table(Meta::Table)
+            .columns([Meta::Id, Meta::LastError])
+            .values_panic([1.into(), error.into()])
+            .on_conflict(
+                OnConflict::column(Meta::Id)
+                    .update_column(Meta::LastError)
+                    .to_owned(),
+            )
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let _ = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error);
+    }
+
+    pub fn get_last_error(&self) -> Option<String> {
+        let (sql, values) = Query::select()
+            .from(Meta::Table)
+            .column(Meta::LastError)
+            .and_where(Expr::col(Meta::Id).eq(1))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        self.connection
+            .query_row(sql.as_str(), &*values.as_params(), |row| row.get(0))
+            .ok()
+            .flatten()
+    }
+
+    /// Seen state of a playlist, kept apart from the rest so every playlist
+    /// keeps its own progress
+    pub fn mark_seen_in_playlist(&self, playlist: &str, id: &str) -> Result<(), StoreError> {
+        let (sql, values) = Query::insert()
+            .into_table(PlaylistSeen::Table)
+            .columns([PlaylistSeen::Playlist, PlaylistSeen::WallpaperId])
+            .values_panic([playlist.into(), id.into()])
+            .on_conflict(
+                OnConflict::columns([PlaylistSeen::Playlist, PlaylistSeen::WallpaperId])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .build_rusqlite(SqliteQueryBuilder);
+
+        self.connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error)
+            .map_err(|_| StoreError::UpdateFailed)?;
+
+        Ok(())
+    }
+
+    pub fn get_seen_in_playlist(&self, playlist: &str) -> HashSet<String> {
+        let (sql, values) = Query::select()
+            .from(PlaylistSeen::Table)
+            .column(PlaylistSeen::WallpaperId)
+            .and_where(Expr::col(PlaylistSeen::Playlist).eq(playlist))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let Ok(mut stmt) = self.connection.prepare(sql.as_str()) else {
+            return HashSet::new();
+        };
+        stmt.query_map(&*values.as_params(), |row| row.get(0))
+            .inspect_err(log_query_error)
+            .map(|rows| rows.filter_map(Result::ok).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn reset_playlist_seen(&self, playlist: &str) {
+        let (sql, values) = Query::delete()
+            .from_table(PlaylistSeen::Table)
+            .and_where(Expr::col(PlaylistSeen::Playlist).eq(playlist))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let _ = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error);
+    }
+
+    /// Switches to a playlist, or back to the whole collection when `None`
+    pub fn set_active_playlist(&self, playlist: Option<&str>) {
+        self.set_meta_text(Meta::ActivePlaylist, playlist);
+    }
+
+    pub fn get_active_playlist(&self) -> Option<String> {
+        self.get_meta_text(Meta::ActivePlaylist)
+    }
+
+    /// Contents of the playlist file found in the source at the last scan
+    pub fn set_source_playlists(&self, contents: Option<&str>) {
+        self.set_meta_text(Meta::SourcePlaylists, contents);
+    }
+
+    pub fn get_source_playlists(&self) -> Option<String> {
+        self.get_meta_text(Meta::SourcePlaylists)
+    }
+
+    /// Contents of the calendar file found in the source at the last scan
+    pub fn set_source_calendar(&self, contents: Option<&str>) {
+        self.set_meta_text(Meta::SourceCalendar, contents);
+    }
+
+    pub fn get_source_calendar(&self) -> Option<String> {
+        self.get_meta_text(Meta::SourceCalendar)
+    }
+
+    fn set_meta_text(&self, column: Meta, value: Option<&str>) {
+        let (sql, values) = Query::insert()
+            .into_table(Meta::Table)
+            .columns([Meta::Id, column])
+            .values_panic([1.into(), value.into()])
+            .on_conflict(
+                OnConflict::column(Meta::Id)
+                    .update_column(column)
+                    .to_owned(),
+            )
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let _ = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error);
+    }
+
+    fn get_meta_text(&self, column: Meta) -> Option<String> {
+        let (sql, values) = Query::select()
+            .from(Meta::Table)
+            .column(column)
+            .and_where(Expr::col(Meta::Id).eq(1))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        self.connection
+            .query_row(sql.as_str(), &*values.as_params(), |row| row.get(0))
+            .ok()
+            .flatten()
+    }
+
+    /// Seed of the order the shuffle strategy walks through in the current cycle
+    pub fn get_cycle_seed(&self) -> Option<u64> {
+        let (sql, values) = Query::select()
+            .from(Meta::Table)
+            .column(Meta::CycleSeed)
+            .and_where(Expr::col(Meta::Id).eq(1))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        self.connection
+            .query_row(sql.as_str(), &*values.as_params(), |row| {
+                row.get::<_, Option<i64>>(0)
+            })
+            .ok()
+            .flatten()
+            .map(|seed| seed as u64)
+    }
+
+    pub fn set_cycle_seed(&self, seed: u64) {
+        let (sql, values) = Query::insert()
+            .into_table(Meta::Table)
+            .columns([Meta::Id, Meta::CycleSeed])
+            // sqlite integers are signed, the bits are what matter
+            .values_panic([1.into(), (seed as i64).into()])
+            .on_conflict(
+                OnConflict::column(Meta::Id)
+                    .update_column(Meta::CycleSeed)
+                    .to_owned(),
+            )
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let _ = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error);
+    }
+
+    /// Pauses wallpaper changes, or resumes them when `None`
+    pub fn set_pause(&self, pause: Option<Pause>) {
+        let until = match pause {
+            Some(Pause::Until(until)) => Some(until.fixed_offset()),
+            _ => None,
+        };
+
+        let (sql, values) = Query::insert()
+            .into_table(Meta::Table)
+            .columns([Meta::Id, Meta::Paused, Meta::PausedUntil])
+            .values_panic([1.into(), pause.is_some().into(), until.into()])
+            .on_conflict(
+                OnConflict::column(Meta::Id)
+                    .update_columns([Meta::Paused, Meta::PausedUntil])
+                    .to_owned(),
+            )
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let _ = self
+            .connection
+            .execute(sql.as_str(), &*values.as_params())
+            .inspect_err(log_query_error);
+    }
+
+    pub fn get_pause(&self) -> Option<Pause> {
+        let (sql, values) = Query::select()
+            .from(Meta::Table)
+            .columns([Meta::Paused, Meta::PausedUntil])
+            .and_where(Expr::col(Meta::Id).eq(1))
+            .build_rusqlite(SqliteQueryBuilder);
+
+        let (paused, until): (bool, Option<DateTime<Local>>) = self
+            .connection
+            .query_row(sql.as_str(), &*values.as_params(), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .ok()?;
+
+        match (paused, until) {
+            (false, _) => None,
+            (true, Some(until)) => Some(Pause::Until(until)),
+            (true, None) => Some(Pause::Indefinite),
+        }
+    }
+
     pub fn get_meta(&self) -> Option<MetaData> {
         let (sql, values) = Query::select()
             .from(Meta::Table)
@@ -315,11 +1123,7 @@ impl Store {
         let manager_id: u8 = get_config().file_config.content_manager_type.into();
         let (sql, values) = Query::select()
             .from(SeenWallpapers::Table)
-            .columns([
-                SeenWallpapers::Id,
-                SeenWallpapers::Seen,
-                SeenWallpapers::ManagerId,
-            ])
+            .columns(WALLPAPER_COLUMNS)
             .and_where(Expr::col(SeenWallpapers::Id).eq(id).to_owned())
             .and_where(
                 Expr::col(SeenWallpapers::ManagerId)
@@ -342,23 +1146,27 @@ pub struct MetaData {
     pub last_used: String,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pause {
+    Indefinite,
+    Until(DateTime<Local>),
+}
+
 #[cfg(test)]
 mod tests {
     use std::error::Error;
-    use std::sync::Once;
+
+    use chrono::TimeDelta;
 
     use crate::CONFIG;
+    use crate::clock::FakeClock;
     use crate::config::Config;
     use crate::content_managers::ContentManagerTypes;
 
     use super::*;
 
-    static SETUP: Once = Once::new();
     pub fn setup() -> Result<Store, Box<dyn Error>> {
-        SETUP.call_once(|| {
-            let config = Config::create_config();
-            CONFIG.set(config).expect("failed to set ");
-        });
+        CONFIG.get_or_init(Config::create_config);
 
         Ok(Store::new()?)
     }
@@ -422,22 +1230,23 @@ mod tests {
 
     #[test]
     fn test_last_update() -> Result<(), Box<dyn Error>> {
-        let store = setup()?;
+        CONFIG.get_or_init(Config::create_config);
+        let clock = Rc::new(FakeClock::new(Local::now()));
+        let store = Store::with_clock(clock.clone())?;
         assert!(store.get_last_update().is_none());
+
         store.update_last_run();
-        let last_update = store.get_last_update();
+        let first = store.get_last_update().expect("last_update not set");
 
-        if let Some(first) = last_update {
-            store.update_last_run();
-            let last_update = store.get_last_update();
+        clock.advance(TimeDelta::minutes(1));
+        store.update_last_run();
+        let second = store.get_last_update().expect("last_update not set");
 
-            if let Some(second) = last_update {
-                assert_ne!(first, second);
-                return Ok(());
-            }
-        }
+        assert_eq!(second - first, TimeDelta::minutes(1));
 
-        panic!("last_update not set");
+        store.clear_last_update();
+        assert!(store.get_last_update().is_none());
+        Ok(())
     }
 
     #[test]
@@ -465,4 +1274,328 @@ mod tests {
 
         panic!("meta not set");
     }
+
+    #[test]
+    fn test_last_error() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        assert!(store.get_last_error().is_none());
+
+        store.set_last_error(Some("backend is not ready"));
+        assert_eq!(
+            store.get_last_error().as_deref(),
+            Some("backend is not ready")
+        );
+
+        store.set_last_error(None);
+        assert!(store.get_last_error().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pause() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        assert!(store.get_pause().is_none());
+
+        store.set_pause(Some(Pause::Indefinite));
+        assert_eq!(store.get_pause(), Some(Pause::Indefinite));
+
+        let until = Local::now() + TimeDelta::hours(1);
+        store.set_pause(Some(Pause::Until(until)));
+        assert_eq!(store.get_pause(), Some(Pause::Until(until)));
+
+        store.set_pause(None);
+        assert!(store.get_pause().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_favorite_and_weight() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        let wallpaper = Wallpaper::new("weighted".to_string(), ContentManagerTypes::Local);
+        store.insert_wallpaper(&wallpaper)?;
+
+        let stored = store.get_wallpaper("weighted").unwrap();
+        assert!(!stored.favorite);
+        assert_eq!(stored.weight, 1.0);
+
+        store.set_favorite("weighted", true)?;
+        store.set_weight("weighted", 2.5)?;
+        let stored = store.get_wallpaper("weighted").unwrap();
+        assert!(stored.favorite);
+        assert_eq!(stored.weight, 2.5);
+
+        // a rescan keeps the flags
+        store.insert_wallpaper(&wallpaper)?;
+        assert!(store.get_wallpaper("weighted").unwrap().favorite);
+
+        assert!(matches!(
+            store.set_favorite("missing", true),
+            Err(StoreError::UnknownWallpaper(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_banned_wallpapers() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        let banned = Wallpaper::new("banned".to_string(), ContentManagerTypes::Local);
+        let kept = Wallpaper::new("kept".to_string(), ContentManagerTypes::Local);
+        store.insert_wallpaper(&banned)?;
+        store.insert_wallpaper(&kept)?;
+
+        store.set_banned("banned", true)?;
+        let unseen = store.get_unseen_wallpaperrs();
+        assert_eq!(unseen.len(), 1);
+        assert_eq!(unseen[0].id, "kept");
+
+        // resets and rescans don't bring it back
+        store.mark_as_seen(&kept)?;
+        store.reset_seen_state();
+        store.insert_wallpaper(&banned)?;
+        let unseen = store.get_unseen_wallpaperrs();
+        assert_eq!(unseen.len(), 1);
+        assert_eq!(unseen[0].id, "kept");
+
+        let bans = store.get_banned_wallpapers();
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0].id, "banned");
+
+        store.set_banned("banned", false)?;
+        assert_eq!(store.get_unseen_wallpaperrs().len(), 2);
+        assert!(store.get_banned_wallpapers().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cycle_seed() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        assert!(store.get_cycle_seed().is_none());
+
+        store.set_cycle_seed(u64::MAX - 1);
+        assert_eq!(store.get_cycle_seed(), Some(u64::MAX - 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wallpaper_dates() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        let mut wallpaper = Wallpaper::new("dated".to_string(), ContentManagerTypes::Local);
+        store.insert_wallpaper(&wallpaper)?;
+        assert!(store.get_wallpaper("dated").unwrap().modified.is_none());
+
+        // a rescan picks up the new modification time
+        wallpaper.modified = Some(1_700_000_000);
+        store.insert_wallpaper(&wallpaper)?;
+        assert_eq!(
+            store.get_wallpaper("dated").unwrap().modified,
+            Some(1_700_000_000)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recent_history() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        assert!(store.get_recent_history(5).is_empty());
+
+        for id in ["first", "second", "third"] {
+            store.add_history(&Wallpaper::new(id.to_string(), ContentManagerTypes::Local));
+        }
+
+        assert_eq!(store.get_recent_history(2), ["third", "second"]);
+        assert_eq!(store.get_recent_history(5), ["third", "second", "first"]);
+        assert!(store.get_recent_history(0).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ratings_and_skips() -> Result<(), Box<dyn Error>> {
+        CONFIG.get_or_init(Config::create_config);
+        let clock = Rc::new(FakeClock::new(Local::now()));
+        let store = Store::with_clock(clock.clone())?;
+        let wallpaper = Wallpaper::new("rated".to_string(), ContentManagerTypes::Local);
+        store.insert_wallpaper(&wallpaper)?;
+
+        store.set_rating("rated", Some(4))?;
+        assert_eq!(store.get_wallpaper("rated").unwrap().rating, Some(4));
+        store.set_rating("rated", None)?;
+        assert_eq!(store.get_wallpaper("rated").unwrap().rating, None);
+        assert!(store.set_rating("missing", Some(1)).is_err());
+
+        assert!(store.record_skip(TimeDelta::minutes(30)).is_none());
+        store.add_history(&wallpaper);
+        clock.advance(TimeDelta::minutes(10));
+        assert_eq!(
+            store.record_skip(TimeDelta::minutes(30)).as_deref(),
+            Some("rated")
+        );
+        // the same showing only counts once
+        assert!(store.record_skip(TimeDelta::minutes(30)).is_none());
+
+        store.add_history(&wallpaper);
+        clock.advance(TimeDelta::hours(1));
+        assert!(store.record_skip(TimeDelta::minutes(30)).is_none());
+
+        let rated = store.get_wallpaper("rated").unwrap();
+        assert_eq!(rated.shows, 2);
+        assert_eq!(rated.skips, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_wallpaper() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        for id in ["old", "copy", "kept"] {
+            store.insert_wallpaper(&Wallpaper {
+                hash: Some("abc".to_string()),
+                ..Wallpaper::new(id.to_string(), ContentManagerTypes::Local)
+            })?;
+        }
+        store.set_favorite("old", true)?;
+        store.add_tag("old", "sunset")?;
+        store.mark_seen_in_playlist("evening", "old")?;
+        store.set_last_used(&Wallpaper::new(
+            "old".to_string(),
+            ContentManagerTypes::Local,
+        ));
+        store.set_banned("copy", true)?;
+        store.set_weight("copy", 3.0)?;
+        store.mark_as_seen(&Wallpaper::new(
+            "copy".to_string(),
+            ContentManagerTypes::Local,
+        ))?;
+        store.set_rating("kept", Some(4))?;
+
+        // a rename keeps everything under the new id
+        store.merge_wallpaper("old", "new")?;
+        assert!(store.get_wallpaper("old").is_none());
+        assert!(store.get_wallpaper("new").unwrap().favorite);
+        assert_eq!(
+            store.get_tags("new"),
+            [Tag::new("sunset", TagSource::Manual)]
+        );
+        assert!(store.get_seen_in_playlist("evening").contains("new"));
+        assert_eq!(store.get_meta().unwrap().last_used, "new");
+
+        // a duplicate is folded into the wallpaper that is kept
+        store.merge_wallpaper("copy", "kept")?;
+        assert!(store.get_wallpaper("copy").is_none());
+        let kept = store.get_wallpaper("kept").unwrap();
+        assert!(kept.banned);
+        assert!(kept.seen);
+        assert_eq!(kept.weight, 3.0);
+        assert_eq!(kept.rating, Some(4));
+
+        assert_eq!(store.get_wallpapers_by_hash("abc").len(), 2);
+        store.merge_wallpaper("missing", "kept")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_rolls_back() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        let insert = |id: &str| {
+            store.insert_wallpaper(&Wallpaper::new(id.to_string(), ContentManagerTypes::Local))
+        };
+
+        let result = store.transaction(|| {
+            insert("outer")?;
+            // a failed inner transaction only undoes its own changes
+            let _ = store.transaction(|| {
+                insert("inner")?;
+                Err::<(), _>(StoreError::UpdateFailed)
+            });
+            assert!(store.get_wallpaper("inner").is_none());
+            assert!(store.get_wallpaper("outer").is_some());
+            Err::<(), _>(StoreError::UpdateFailed)
+        });
+
+        assert!(result.is_err());
+        assert!(store.get_wallpaper("outer").is_none());
+        store.transaction(|| insert("committed"))?;
+        assert!(store.get_wallpaper("committed").is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        let wallpaper = Wallpaper::new("tagged".to_string(), ContentManagerTypes::Local);
+        store.insert_wallpaper(&wallpaper)?;
+
+        store.set_scanned_tags(
+            "tagged",
+            &[
+                Tag::new("nature", TagSource::Directory),
+                Tag::new("calm", TagSource::Sidecar),
+            ],
+        )?;
+        store.add_tag("tagged", "Favourite")?;
+        assert!(store.add_tag("missing", "nature").is_err());
+
+        let names = |store: &Store| {
+            let mut names = store
+                .get_all_tags()
+                .remove("tagged")
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&store), ["calm", "favourite", "nature"]);
+
+        // a rescan replaces scanned tags but keeps manual ones
+        store.set_scanned_tags("tagged", &[Tag::new("green", TagSource::Index)])?;
+        assert_eq!(names(&store), ["favourite", "green"]);
+        assert_eq!(
+            store.get_tags("tagged"),
+            [
+                Tag::new("favourite", TagSource::Manual),
+                Tag::new("green", TagSource::Index)
+            ]
+        );
+
+        assert!(store.remove_tag("tagged", "favourite")?);
+        assert!(!store.remove_tag("tagged", "green")?);
+        assert_eq!(names(&store), ["green"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_playlist_seen_state() -> Result<(), Box<dyn Error>> {
+        let store = setup()?;
+        assert!(store.get_active_playlist().is_none());
+        store.set_active_playlist(Some("work"));
+        assert_eq!(store.get_active_playlist().as_deref(), Some("work"));
+
+        store.mark_seen_in_playlist("work", "a.png")?;
+        store.mark_seen_in_playlist("work", "a.png")?;
+        store.mark_seen_in_playlist("weekend", "b.png")?;
+        assert_eq!(
+            store.get_seen_in_playlist("work"),
+            HashSet::from(["a.png".to_string()])
+        );
+
+        // resetting one playlist leaves the others alone
+        store.reset_playlist_seen("work");
+        assert!(store.get_seen_in_playlist("work").is_empty());
+        assert_eq!(store.get_seen_in_playlist("weekend").len(), 1);
+
+        store.set_active_playlist(None);
+        assert!(store.get_active_playlist().is_none());
+
+        Ok(())
+    }
 }
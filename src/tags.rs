@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::CALENDAR_FILE;
+use crate::playlists::PLAYLIST_FILE;
+
+/// Index file at the root of a wallpaper source, mapping wallpaper ids to tags
+pub const INDEX_FILE: &str = "tags.toml";
+/// Extension of sidecar files, `forest.png.tags` holds the tags of `forest.png`
+pub const SIDECAR_EXTENSION: &str = "tags";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagSource {
+    Directory,
+    Sidecar,
+    Index,
+    // added from the cli, kept across rescans
+    Manual,
+}
+
+impl TagSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TagSource::Directory => "directory",
+            TagSource::Sidecar => "sidecar",
+            TagSource::Index => "index",
+            TagSource::Manual => "manual",
+        }
+    }
+}
+
+impl Display for TagSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl TryFrom<&str> for TagSource {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "directory" => Ok(TagSource::Directory),
+            "sidecar" => Ok(TagSource::Sidecar),
+            "index" => Ok(TagSource::Index),
+            "manual" => Ok(TagSource::Manual),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tag {
+    pub name: String,
+    pub source: TagSource,
+}
+
+impl Tag {
+    pub fn new(name: &str, source: TagSource) -> Tag {
+        Tag {
+            name: normalize(name),
+            source,
+        }
+    }
+}
+
+/// Wallpapers with any of the `include` tags, or all of them when it is
+/// empty, leaving out those with any of the `exclude` tags
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TagFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, tags: Option<&HashSet<String>>) -> bool {
+        let has = |tag: &String| tags.is_some_and(|tags| tags.contains(&normalize(tag)));
+        (self.include.is_empty() || self.include.iter().any(has)) && !self.exclude.iter().any(has)
+    }
+}
+
+pub fn normalize(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Splits the files of a wallpaper source, relative to its root and `/`
+/// separated, into wallpapers and tag files, and works out the tags of each
+/// wallpaper. `read_file` reads a tag file by that same relative path
+pub fn tag_wallpapers(
+    files: Vec<String>,
+    read_file: impl Fn(&str) -> Option<String>,
+) -> Vec<(String, Vec<Tag>)> {
+    let file_set = files.iter().cloned().collect::<HashSet<_>>();
+    let index = match file_set
+        .contains(INDEX_FILE)
+        .then(|| read_file(INDEX_FILE))
+        .flatten()
+        .map(|index| parse_index(&index))
+    {
+        Some(Ok(index)) => index,
+        Some(Err(err)) => {
+            tracing::warn!("ignoring {}: {}", INDEX_FILE, err);
+            HashMap::new()
+        }
+        None => HashMap::new(),
+    };
+
+    files
+        .into_iter()
+        .filter(|file| !is_source_metadata(file))
+        .map(|file| {
+            let mut tags = directory_tags(&file);
+
+            let sidecar = format!("{file}.{SIDECAR_EXTENSION}");
+            if file_set.contains(&sidecar)
+                && let Some(contents) = read_file(&sidecar)
+            {
+                tags.extend(parse_sidecar(&contents));
+            }
+
+            if let Some(names) = index.get(&file) {
+                tags.extend(names.iter().map(|name| Tag::new(name, TagSource::Index)));
+            }
+
+            let mut seen = HashSet::new();
+            tags.retain(|tag| !tag.name.is_empty() && seen.insert(tag.clone()));
+            (file, tags)
+        })
+        .collect()
+}
+
+// tag, playlist and calendar files live next to the wallpapers but aren't any
+fn is_source_metadata(file: &str) -> bool {
+    file == INDEX_FILE
+        || file == PLAYLIST_FILE
+        || file == CALENDAR_FILE
+        || Path::new(file)
+            .extension()
+            .is_some_and(|extension| extension == SIDECAR_EXTENSION)
+}
+
+// every directory a wallpaper is nested in is a tag, `space/nebula/a.png` is tagged space and nebula
+fn directory_tags(file: &str) -> Vec<Tag> {
+    let mut components = file.split('/').collect::<Vec<_>>();
+    components.pop();
+    components
+        .into_iter()
+        .map(|name| Tag::new(name, TagSource::Directory))
+        .collect()
+}
+
+// tags separated by whitespace, commas or new lines
+fn parse_sidecar(contents: &str) -> Vec<Tag> {
+    contents
+        .split(|char: char| char.is_whitespace() || char == ',')
+        .map(|name| Tag::new(name, TagSource::Sidecar))
+        .collect()
+}
+
+fn parse_index(contents: &str) -> Result<HashMap<String, Vec<String>>, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(tags: &[Tag], source: TagSource) -> Vec<&str> {
+        tags.iter()
+            .filter(|tag| tag.source == source)
+            .map(|tag| tag.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_tag_wallpapers() {
+        let files = [
+            "top.png",
+            "Space/nebula/orion.png",
+            "Space/nebula/orion.png.tags",
+            "nature/forest.jpg",
+            "tags.toml",
+            "playlists.toml",
+            "calendar.toml",
+        ]
+        .map(String::from)
+        .to_vec();
+        let read_file = |file: &str| match file {
+            "Space/nebula/orion.png.tags" => Some("Purple, stars\ncalm".to_string()),
+            "tags.toml" => Some(
+                "\"nature/forest.jpg\" = [\"green\", \"calm\"]\n\"top.png\" = [\"minimal\"]"
+                    .to_string(),
+            ),
+            _ => None,
+        };
+
+        let wallpapers = tag_wallpapers(files, read_file);
+        let ids = wallpapers
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            ["top.png", "Space/nebula/orion.png", "nature/forest.jpg"]
+        );
+
+        let (_, top) = &wallpapers[0];
+        assert_eq!(names(top, TagSource::Directory), Vec::<&str>::new());
+        assert_eq!(names(top, TagSource::Index), ["minimal"]);
+
+        let (_, orion) = &wallpapers[1];
+        assert_eq!(names(orion, TagSource::Directory), ["space", "nebula"]);
+        assert_eq!(
+            names(orion, TagSource::Sidecar),
+            ["purple", "stars", "calm"]
+        );
+
+        let (_, forest) = &wallpapers[2];
+        assert_eq!(names(forest, TagSource::Directory), ["nature"]);
+        assert_eq!(names(forest, TagSource::Index), ["green", "calm"]);
+    }
+
+    #[test]
+    fn test_broken_index_is_ignored() {
+        let files = vec!["a.png".to_string(), "tags.toml".to_string()];
+        let wallpapers = tag_wallpapers(files, |_| Some("not = [valid".to_string()));
+
+        assert_eq!(wallpapers.len(), 1);
+        assert!(wallpapers[0].1.is_empty());
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let tags = ["nature", "calm"].map(String::from).into_iter().collect();
+        let filter = |include: &[&str], exclude: &[&str]| TagFilter {
+            include: include.iter().map(|tag| tag.to_string()).collect(),
+            exclude: exclude.iter().map(|tag| tag.to_string()).collect(),
+        };
+
+        assert!(filter(&[], &[]).matches(Some(&tags)));
+        assert!(filter(&[], &[]).matches(None));
+        assert!(filter(&["space", "Nature"], &[]).matches(Some(&tags)));
+        assert!(!filter(&["space"], &[]).matches(Some(&tags)));
+        assert!(!filter(&["space"], &[]).matches(None));
+        assert!(!filter(&["nature"], &["calm"]).matches(Some(&tags)));
+        assert!(filter(&[], &["minimal"]).matches(None));
+    }
+}
@@ -1,17 +1,28 @@
 use rand::RngExt;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
-use std::thread::sleep;
-use std::time::Duration;
 
 use thiserror::Error;
 
-use crate::backends::WallpaperBackend;
+use crate::backends::{WallpaperBackend, WallpaperBackendError};
+use crate::calendar::{self, CALENDAR_FILE, DateOverride};
+use crate::config::SelectionStrategy;
 use crate::content_managers::ContentManagerTypes;
 use crate::content_managers::git::GitContentManager;
 use crate::content_managers::local::LocalContentManager;
 use crate::get_config;
-use crate::store::Store;
+use crate::hash;
+use crate::playlists::{self, PLAYLIST_FILE, Playlist, PlaylistOrder};
+use crate::retry::RetryPolicy;
+use crate::schedule;
+use crate::seasons::{self, SeasonalCollections};
+use crate::selection;
+use crate::store::{DatabaseWallpaper, Pause, Store, StoreError};
+use crate::tags::{Tag, TagFilter};
+
+// how many unreadable wallpapers are skipped before giving up on a change
+const MAX_UNREADABLE_SKIPS: u32 = 10;
 
 #[derive(Debug, thiserror::Error)]
 pub enum WallpaperContentManagerError {
@@ -25,7 +36,7 @@ pub enum ContentManager {
 }
 
 impl WallpaperContentManager for ContentManager {
-    fn get_wallpapers(&self) -> Result<Vec<Wallpaper>, WallpaperContentManagerError> {
+    fn get_wallpapers(&self) -> Result<SourceScan, WallpaperContentManagerError> {
         match self {
             ContentManager::Git(manager) => manager.get_wallpapers(),
             ContentManager::Local(manager) => manager.get_wallpapers(),
@@ -40,13 +51,58 @@ impl WallpaperContentManager for ContentManager {
     }
 }
 
+/// What the daemon loop carries over from one check to the next
+pub struct LoopState {
+    // the wallpaper on screen may be stale, e.g. after a suspend
+    pub needs_restore: bool,
+    backend_ready: bool,
+    paused: bool,
+}
+
+impl LoopState {
+    pub fn new(needs_restore: bool) -> LoopState {
+        LoopState {
+            needs_restore,
+            backend_ready: true,
+            paused: false,
+        }
+    }
+}
+
 pub struct WallpapersManager<'a> {
     store: &'a Store,
     backend: Box<dyn WallpaperBackend>,
+    retry_policy: RetryPolicy,
+    // only wallpapers matching it are picked
+    filter: TagFilter,
+    // narrows the filter down further to the current season or month
+    seasonal: SeasonalCollections,
+}
+
+/// Wallpapers found in a source, along with the playlist and calendar files
+/// read from the same copy of it
+pub struct SourceScan {
+    pub wallpapers: Vec<Wallpaper>,
+    pub playlists: Option<String>,
+    pub calendar: Option<String>,
+}
+
+impl SourceScan {
+    // `read_file` gets a path relative to the wallpapers
+    pub fn new(
+        wallpapers: Vec<Wallpaper>,
+        read_file: impl Fn(&str) -> Option<String>,
+    ) -> SourceScan {
+        SourceScan {
+            wallpapers,
+            playlists: read_file(PLAYLIST_FILE),
+            calendar: read_file(CALENDAR_FILE),
+        }
+    }
 }
 
 pub trait WallpaperContentManager {
-    fn get_wallpapers(&self) -> Result<Vec<Wallpaper>, WallpaperContentManagerError>;
+    fn get_wallpapers(&self) -> Result<SourceScan, WallpaperContentManagerError>;
     // optional function to cleanup wallpapers when no longer needed
     fn cleanup_wallpaper(&self, wallpaper: Wallpaper) -> bool;
 }
@@ -59,6 +115,9 @@ impl<'a> WallpapersManager<'a> {
         WallpapersManager {
             store,
             backend: Box::new(backend),
+            retry_policy: RetryPolicy::from_config(&get_config().file_config.retry),
+            filter: get_config().file_config.tags.clone(),
+            seasonal: get_config().file_config.seasonal.clone(),
         }
     }
 
@@ -66,83 +125,532 @@ impl<'a> WallpapersManager<'a> {
         &self,
         content_manager: &impl WallpaperContentManager,
     ) -> Result<(), WallpapersMangerError> {
-        let wallpapers = content_manager
+        let scan = content_manager
             .get_wallpapers()
             .map_err(|_| WallpapersMangerError::GetWallpaperError)?;
-        for wallpaper in wallpapers {
-            tracing::trace!("inserting wallpaper {} to store", wallpaper.id,);
-            self.store
-                .insert_wallpaper(&wallpaper)
-                .map_err(|_| WallpapersMangerError::DatabaseInsertError)?;
+        self.sync_wallpapers(scan.wallpapers)
+            .map_err(|_| WallpapersMangerError::DatabaseInsertError)?;
+
+        if let Some(Err(err)) = scan.playlists.as_deref().map(playlists::parse) {
+            tracing::warn!("ignoring {}: {}", PLAYLIST_FILE, err);
         }
+        self.store.set_source_playlists(scan.playlists.as_deref());
+
+        if let Some(Err(err)) = scan.calendar.as_deref().map(calendar::parse) {
+            tracing::warn!("ignoring {}: {}", CALENDAR_FILE, err);
+        }
+        self.store.set_source_calendar(scan.calendar.as_deref());
 
         Ok(())
     }
 
-    pub fn set_next_wallpaper(&mut self, content_manager: &impl WallpaperContentManager) {
+    /// Stores scanned wallpapers as the ones in the source. Of the
+    /// wallpapers with the same content only the first by id is kept, with
+    /// the tags of all of them, and a stored wallpaper with the same content
+    /// that is no longer in the source is taken to be renamed, so its state
+    /// carries over
+    fn sync_wallpapers(&self, mut wallpapers: Vec<Wallpaper>) -> Result<(), StoreError> {
+        wallpapers.sort_by(|a, b| a.id.cmp(&b.id));
+        wallpapers.dedup_by(|a, b| a.id == b.id);
+        for wallpaper in &mut wallpapers {
+            if wallpaper.hash.is_none() && wallpaper.type_id == ContentManagerTypes::Local {
+                wallpaper.hash = self.local_hash(wallpaper);
+            }
+        }
+        let scanned = wallpapers
+            .iter()
+            .map(|wallpaper| wallpaper.id.clone())
+            .collect::<HashSet<_>>();
+
+        let mut kept: Vec<Wallpaper> = vec![];
+        let mut kept_by_hash: HashMap<String, usize> = HashMap::new();
+        // duplicate ids with the id of the wallpaper kept in their place
+        let mut duplicates = vec![];
+        for wallpaper in wallpapers {
+            let Some(&index) = wallpaper
+                .hash
+                .as_ref()
+                .and_then(|hash| kept_by_hash.get(hash))
+            else {
+                if let Some(hash) = &wallpaper.hash {
+                    kept_by_hash.insert(hash.clone(), kept.len());
+                }
+                kept.push(wallpaper);
+                continue;
+            };
+
+            let original = &mut kept[index];
+            tracing::info!(
+                "{} is a duplicate of {}, leaving it out",
+                wallpaper.id,
+                original.id
+            );
+            for tag in wallpaper.tags {
+                if !original.tags.contains(&tag) {
+                    original.tags.push(tag);
+                }
+            }
+            duplicates.push((wallpaper.id, original.id.clone()));
+        }
+
+        // all at once, so an interrupted scan can't leave wallpapers half merged
+        self.store.transaction(|| {
+            self.store.clear_source_wallpapers()?;
+            for (duplicate, original) in &duplicates {
+                self.store.merge_wallpaper(duplicate, original)?;
+            }
+
+            for wallpaper in &kept {
+                if let Some(hash) = &wallpaper.hash {
+                    for stored in self.store.get_wallpapers_by_hash(hash) {
+                        if stored.id != wallpaper.id && !scanned.contains(&stored.id) {
+                            tracing::info!("{} was renamed to {}", stored.id, wallpaper.id);
+                            self.store.merge_wallpaper(&stored.id, &wallpaper.id)?;
+                        }
+                    }
+                }
+
+                tracing::trace!("inserting wallpaper {} to store", wallpaper.id);
+                self.store.insert_wallpaper(wallpaper)?;
+                self.store
+                    .set_scanned_tags(&wallpaper.id, &wallpaper.tags)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    // the hash stored for a local file is reused for as long as its modified
+    // time stays the same, only new or changed files are read
+    fn local_hash(&self, wallpaper: &Wallpaper) -> Option<String> {
+        let unchanged = self
+            .store
+            .get_wallpaper(&wallpaper.id)
+            .filter(|stored| stored.modified.is_some() && stored.modified == wallpaper.modified)
+            .and_then(|stored| stored.hash);
+        if unchanged.is_some() {
+            return unchanged;
+        }
+
+        let path = PathBuf::from(&get_config().file_config.local.path).join(&wallpaper.id);
+        hash::file_blob_id(&path)
+            .inspect_err(|err| tracing::warn!("failed to hash {}: {}", wallpaper.id, err))
+            .ok()
+    }
+
+    pub fn set_next_wallpaper(
+        &mut self,
+        content_manager: &impl WallpaperContentManager,
+    ) -> Result<(), WallpaperBackendError> {
         tracing::debug!("setting next wallpaper");
-        let mut unseen_wallpapers = self.store.get_unseen_wallpaperrs();
+
+        let rotation = self.get_rotation();
+        let mut attempts = 0;
+        // overrides have no seen state to move them past an unreadable wallpaper
+        let mut unreadable = HashSet::new();
+        let next_wallpaper = loop {
+            let next_wallpaper = match &rotation {
+                Rotation::Override(candidates) => {
+                    self.pick_override_wallpaper(candidates, &unreadable)
+                }
+                Rotation::Playlist(name, playlist) => self.pick_playlist_wallpaper(name, playlist),
+                Rotation::All => self.pick_next_wallpaper(attempts),
+            };
+            let Some(next_wallpaper) = next_wallpaper else {
+                tracing::info!("no wallpapers found in internal store");
+                return Ok(());
+            };
+
+            match self.set_wallpaper_with_retry(&next_wallpaper) {
+                Ok(()) => break next_wallpaper,
+                // a broken image shouldn't hold up the rotation, move on to another one
+                Err(err @ WallpaperBackendError::FileUnreadable(_))
+                    if attempts < MAX_UNREADABLE_SKIPS =>
+                {
+                    tracing::warn!("{}, skipping to another wallpaper", err);
+                    let _ = self.mark_as_seen(&rotation, &next_wallpaper);
+                    unreadable.insert(next_wallpaper.id);
+                    attempts += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        };
+
+        let _ = self
+            .mark_as_seen(&rotation, &next_wallpaper)
+            .inspect_err(|err| {
+                tracing::error!("failed to mark wallpaper as seen: {}", err);
+            });
+        let current_wallpaper = self.get_current_wallpaper();
+        self.store.set_last_used(&next_wallpaper);
+        self.store.add_history(&next_wallpaper);
+        self.store.update_last_run();
+        if let Some(wallpaper) = current_wallpaper {
+            tracing::info!("cleaning up last used wallpaper");
+            content_manager.cleanup_wallpaper(wallpaper);
+        }
+
+        Ok(())
+    }
+
+    /// What to pick from: a date override for today takes precedence over
+    /// the playlist switched to with `mirai playlist use`, which takes
+    /// precedence over the whole collection
+    fn get_rotation(&self) -> Rotation {
+        let today = self.store.clock().now().date_naive();
+        if let Some(date_override) = calendar::active_override(self.store, today) {
+            let candidates = self.get_override_wallpapers(&date_override);
+            if !candidates.is_empty() {
+                return Rotation::Override(candidates);
+            }
+            tracing::warn!(
+                "no wallpapers match the override starting {}, ignoring it",
+                date_override.start
+            );
+        }
+
+        let Some(name) = self.store.get_active_playlist() else {
+            return Rotation::All;
+        };
+        match playlists::all(self.store).remove(&name) {
+            Some(playlist) => Rotation::Playlist(name, playlist),
+            None => {
+                tracing::warn!("playlist {} no longer exists, using all wallpapers", name);
+                Rotation::All
+            }
+        }
+    }
+
+    // each playlist keeps its own seen state, apart from the whole collection.
+    // overrides are shown outside of either, so the rotation carries on where
+    // it was once they end
+    fn mark_as_seen(&self, rotation: &Rotation, wallpaper: &Wallpaper) -> Result<(), StoreError> {
+        match rotation {
+            Rotation::Override(_) => Ok(()),
+            Rotation::Playlist(name, _) => self.store.mark_seen_in_playlist(name, &wallpaper.id),
+            Rotation::All => self.store.mark_as_seen(wallpaper),
+        }
+    }
+
+    fn get_override_wallpapers(&self, date_override: &DateOverride) -> Vec<DatabaseWallpaper> {
+        let config = &get_config().file_config.selection;
+        let tags = self.store.get_all_tags();
+        let tag_filter = date_override.tag.clone().map(|tag| TagFilter {
+            include: vec![tag],
+            exclude: vec![],
+        });
+
+        self.store
+            .get_source_wallpapers()
+            .into_iter()
+            .filter(|wallpaper| !wallpaper.banned && selection::is_pickable(wallpaper, config))
+            .filter(|wallpaper| {
+                date_override.wallpaper.as_ref() == Some(&wallpaper.id)
+                    || tag_filter
+                        .as_ref()
+                        .is_some_and(|filter| filter.matches(tags.get(&wallpaper.id)))
+            })
+            .collect()
+    }
+
+    // seeded instances still agree on the pick, the others avoid repeats
+    // between days of a longer override
+    fn pick_override_wallpaper(
+        &self,
+        candidates: &[DatabaseWallpaper],
+        unreadable: &HashSet<String>,
+    ) -> Option<Wallpaper> {
+        let config = &get_config().file_config.selection;
+        let next_wallpaper = if config.strategy == SelectionStrategy::Seeded {
+            let ids = candidates
+                .iter()
+                .map(|wallpaper| wallpaper.id.clone())
+                .collect::<Vec<_>>();
+            let slot = schedule::slot(
+                get_config().file_config.update_interval,
+                &self.store.clock().now(),
+            );
+            let start = selection::seeded_index(&ids, &config.seed, slot)?;
+            (0..candidates.len())
+                .map(|offset| &candidates[(start + offset) % candidates.len()])
+                .find(|wallpaper| !unreadable.contains(&wallpaper.id))?
+                .clone()
+        } else {
+            let candidates = candidates
+                .iter()
+                .filter(|wallpaper| !unreadable.contains(&wallpaper.id))
+                .cloned()
+                .collect();
+            let recent = self.store.get_recent_history(config.no_repeat_window);
+            let candidates = selection::exclude_recent(candidates, &recent);
+            let index = selection::pick_weighted(&candidates, config, &mut rand::rng())?;
+            candidates[index].clone()
+        };
+
+        next_wallpaper.try_into().ok()
+    }
+
+    // the tag filter and selection strategy are left out, the playlist
+    // already says what to show and in which order
+    fn pick_playlist_wallpaper(&self, name: &str, playlist: &Playlist) -> Option<Wallpaper> {
+        let unseen = |seen: &HashSet<String>| {
+            playlist
+                .wallpapers
+                .iter()
+                .filter(|id| !seen.contains(*id))
+                .filter_map(|id| self.store.get_wallpaper(id))
+                .filter(|wallpaper| {
+                    wallpaper.in_source
+                        && !wallpaper.banned
+                        && selection::is_pickable(wallpaper, &get_config().file_config.selection)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut unseen_wallpapers = unseen(&self.store.get_seen_in_playlist(name));
+        if unseen_wallpapers.is_empty() {
+            tracing::info!(
+                "all wallpapers in playlist {} have been seen, starting over",
+                name
+            );
+            self.store.reset_playlist_seen(name);
+            unseen_wallpapers = unseen(&HashSet::new());
+        }
+
+        if unseen_wallpapers.is_empty() {
+            tracing::warn!("playlist {} has no known wallpapers", name);
+            return None;
+        }
+
+        let index = match playlist.order {
+            PlaylistOrder::Sequential => 0,
+            PlaylistOrder::Random => selection::pick_weighted(
+                &unseen_wallpapers,
+                &get_config().file_config.selection,
+                &mut rand::rng(),
+            )?,
+        };
+
+        unseen_wallpapers.swap_remove(index).try_into().ok()
+    }
+
+    // `attempt` counts the wallpapers skipped for this change so far
+    fn pick_next_wallpaper(&self, attempt: u32) -> Option<Wallpaper> {
+        if get_config().file_config.selection.strategy == SelectionStrategy::Seeded {
+            return self.pick_seeded_wallpaper(attempt);
+        }
+
+        let mut unseen_wallpapers = self.get_unseen_wallpapers();
         tracing::debug!("{} unseen wallpapers", unseen_wallpapers.len());
 
+        let mut rng = rand::rng();
         if unseen_wallpapers.is_empty() {
             tracing::info!("all wallpapers have been seen, resetting seen state");
             self.store.reset_seen_state();
-            unseen_wallpapers = self.store.get_unseen_wallpaperrs();
+            // a new cycle gets a new shuffle order
+            self.store.set_cycle_seed(rng.random());
+            unseen_wallpapers = self.get_unseen_wallpapers();
         }
 
         if unseen_wallpapers.is_empty() {
-            tracing::info!("no wallpapers found in internal store");
-            return;
+            if !self.filter.is_empty() {
+                tracing::warn!("no wallpapers match the tag filter");
+            }
+            if self.in_season().is_some() {
+                tracing::warn!("no wallpapers are in the collection in season");
+            }
+            return None;
         }
 
-        let mut rng = rand::rng();
-        let random_index = rng.random_range(0..unseen_wallpapers.len());
+        let config = &get_config().file_config.selection;
+        let recent = self.store.get_recent_history(config.no_repeat_window);
+        let unseen_wallpapers = selection::exclude_recent(unseen_wallpapers, &recent);
+
+        let cycle_seed = self.store.get_cycle_seed().unwrap_or_else(|| {
+            let seed = rng.random();
+            self.store.set_cycle_seed(seed);
+            seed
+        });
+        let index = selection::pick_next(&unseen_wallpapers, config, cycle_seed, &mut rng)?;
 
-        let next_wallpaper_db = &unseen_wallpapers[random_index];
+        let next_wallpaper_db = &unseen_wallpapers[index];
         let next_wallpaper: Wallpaper = next_wallpaper_db
             .clone()
             .try_into()
             .expect("database has unsupported manager id. this is a bug");
-        let _ = self
-            .backend
-            .set_wallpaper(&next_wallpaper)
-            .inspect_err(|err| {
-                tracing::error!("failed to set wallpaper: {}", err);
-            });
-        let _ = self.store.mark_as_seen(&next_wallpaper).inspect_err(|err| {
-            tracing::error!("failed to mark wallpaper as seen: {}", err);
+        Some(next_wallpaper)
+    }
+
+    fn get_unseen_wallpapers(&self) -> Vec<DatabaseWallpaper> {
+        // zero weights are left out here, so a cycle ends once only they are left
+        let config = &get_config().file_config.selection;
+        let mut unseen_wallpapers = self.store.get_unseen_wallpaperrs();
+        unseen_wallpapers.retain(|wallpaper| selection::is_pickable(wallpaper, config));
+        self.retain_filtered(&mut unseen_wallpapers, |wallpaper| &wallpaper.id);
+        unseen_wallpapers
+    }
+
+    fn in_season(&self) -> Option<&[String]> {
+        self.seasonal
+            .in_season(self.store.clock().now().date_naive())
+    }
+
+    // a wallpaper has to match the tag filter and be in the collection in season, if any
+    fn retain_filtered<T>(&self, wallpapers: &mut Vec<T>, id: fn(&T) -> &String) {
+        let in_season = self.in_season();
+        if self.filter.is_empty() && in_season.is_none() {
+            return;
+        }
+
+        let tags = self.store.get_all_tags();
+        wallpapers.retain(|wallpaper| {
+            let id = id(wallpaper);
+            let tags = tags.get(id);
+            self.filter.matches(tags)
+                && in_season.is_none_or(|collection| seasons::matches(collection, id, tags))
         });
-        let current_wallpaper = self.get_current_wallpaper();
-        self.store.set_last_used(&next_wallpaper);
-        self.store.update_last_run();
-        if let Some(wallpaper) = current_wallpaper {
-            tracing::info!("cleaning up last used wallpaper");
-            content_manager.cleanup_wallpaper(wallpaper);
+    }
+
+    // the pick only depends on the seed, the slot and the wallpapers in the
+    // source as of the last scan, never on local state, so separate instances agree
+    fn pick_seeded_wallpaper(&self, attempt: u32) -> Option<Wallpaper> {
+        let mut wallpapers = self.store.get_source_wallpapers();
+        self.retain_filtered(&mut wallpapers, |wallpaper| &wallpaper.id);
+
+        let ids = wallpapers
+            .iter()
+            .map(|wallpaper| wallpaper.id.clone())
+            .collect::<Vec<_>>();
+        let slot = schedule::slot(
+            get_config().file_config.update_interval,
+            &self.store.clock().now(),
+        );
+        let start = selection::seeded_index(&ids, &get_config().file_config.selection.seed, slot)?;
+
+        // banned and unreadable wallpapers move the pick along to the next one
+        (0..wallpapers.len())
+            .map(|offset| &wallpapers[(start + attempt as usize + offset) % wallpapers.len()])
+            .find(|wallpaper| !wallpaper.banned)
+            .cloned()?
+            .try_into()
+            .ok()
+    }
+
+    pub fn set_last_wallpaper(&self) -> Result<(), WallpaperBackendError> {
+        match self.get_current_wallpaper() {
+            Some(wallpaper) => self.set_wallpaper_with_retry(&wallpaper),
+            None => Ok(()),
         }
     }
 
-    pub fn set_last_wallpaper(&self) {
-        let wallpaper = self.get_current_wallpaper();
-        if wallpaper.is_none() {
-            return;
+    // failures are recorded in the store, so they outlive the log line
+    fn set_wallpaper_with_retry(&self, wallpaper: &Wallpaper) -> Result<(), WallpaperBackendError> {
+        let result = self.retry_policy.retry(self.backend.as_ref(), || {
+            self.backend.set_wallpaper(wallpaper)
+        });
+
+        match &result {
+            Ok(()) => self.store.set_last_error(None),
+            Err(err) => {
+                tracing::error!("failed to set wallpaper {}: {}", wallpaper.id, err);
+                self.store.set_last_error(Some(&err.to_string()));
+            }
         }
-        let wallpaper = wallpaper.unwrap();
 
-        let mut times = 0;
-        loop {
-            if self.backend.set_wallpaper(&wallpaper).is_ok() {
-                return;
-            };
+        result
+    }
+
+    pub fn should_update_wallpaper(&self) -> bool {
+        let now = self.store.clock().now();
+        let last_update = self.store.get_last_update();
+        schedule::should_update_wallpaper(
+            get_config().file_config.update_interval,
+            last_update,
+            now,
+        )
+    }
+
+    /// Moves the last run back to now if the clock was set back past it, so
+    /// the schedule carries on from the current time instead of changing
+    /// the wallpaper straight away
+    pub fn resync_schedule(&self) {
+        let now = self.store.clock().now();
+        if self
+            .store
+            .get_last_update()
+            .is_some_and(|last_update| last_update > now)
+        {
+            self.store.update_last_run();
+        }
+    }
+
+    /// Whether wallpaper changes are paused, resuming them once the pause
+    /// has run out
+    pub fn is_paused(&self) -> bool {
+        match self.store.get_pause() {
+            Some(Pause::Until(until)) if until <= self.store.clock().now() => {
+                tracing::info!("pause ended at {}, resuming", until);
+                self.store.set_pause(None);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
 
-            if times > 5 {
-                panic!(
-                    "failed to set wallpaper 5 times, please make sure your chosen backend is available",
-                );
+    pub fn is_backend_ready(&self) -> bool {
+        self.backend.is_ready()
+    }
+
+    /// One pass of the daemon loop: notices pauses and the backend going
+    /// away or coming back, then changes the wallpaper when it is due or
+    /// restores the current one when it may be stale
+    pub fn check(&mut self, state: &mut LoopState, content_manager: &impl WallpaperContentManager) {
+        // paused from the cli, the current wallpaper stays but is still restored
+        let paused = self.is_paused();
+        if paused != state.paused {
+            if paused {
+                tracing::info!("wallpaper changes are paused");
+            } else {
+                tracing::info!("wallpaper changes are resumed");
             }
+            state.paused = paused;
+        }
 
-            times += 1;
-            tracing::warn!("failed to set wallpaper, retrying in 5 seconds");
-            sleep(Duration::from_secs(5));
+        // the backend can go away at any time, e.g. when the swww daemon restarts
+        let ready = self.is_backend_ready();
+        if ready != state.backend_ready {
+            if ready {
+                tracing::info!("backend is ready again, re-applying current wallpaper");
+                state.needs_restore = true;
+            } else {
+                tracing::warn!("backend is no longer ready, waiting for it to come back");
+                self.store.set_last_error(Some("backend is not ready"));
+            }
+            state.backend_ready = ready;
+        }
+        if !state.backend_ready {
+            return;
+        }
+
+        if !state.paused && self.should_update_wallpaper() {
+            match self.set_next_wallpaper(content_manager) {
+                Ok(()) => state.needs_restore = false,
+                Err(err) => tracing::warn!("wallpaper change failed ({}), retrying shortly", err),
+            }
+        } else if state.needs_restore {
+            match self.set_last_wallpaper() {
+                Ok(()) => state.needs_restore = false,
+                Err(err) if err.is_retryable() => {
+                    tracing::warn!("could not restore last wallpaper, retrying shortly")
+                }
+                Err(_) => {
+                    tracing::info!("could not restore last wallpaper, picking a new one");
+                    if self.set_next_wallpaper(content_manager).is_ok() {
+                        state.needs_restore = false;
+                    }
+                }
+            }
         }
     }
 
@@ -158,6 +666,13 @@ impl<'a> WallpapersManager<'a> {
     }
 }
 
+enum Rotation {
+    // wallpapers pinned to today
+    Override(Vec<DatabaseWallpaper>),
+    Playlist(String, Playlist),
+    All,
+}
+
 #[derive(Debug, Error)]
 pub enum WallpapersMangerError {
     #[error("failed to add wallpaper to internal database")]
@@ -170,26 +685,891 @@ pub enum WallpapersMangerError {
 pub struct Wallpaper {
     pub id: String,
     pub type_id: ContentManagerTypes,
+    // unix timestamps used to order wallpapers, when the source knows them
+    pub modified: Option<i64>,
+    pub committed: Option<i64>,
+    // git blob id of the content, identifies the wallpaper across renames
+    pub hash: Option<String>,
+    // tags found while scanning the source
+    pub tags: Vec<Tag>,
 }
 
 impl Wallpaper {
     pub fn new(id: String, type_id: ContentManagerTypes) -> Wallpaper {
-        Wallpaper { id, type_id }
+        Wallpaper {
+            id,
+            type_id,
+            modified: None,
+            committed: None,
+            hash: None,
+            tags: vec![],
+        }
     }
 
     pub fn get_wallpaper_path(&self) -> Result<PathBuf, ()> {
         match self.type_id {
             ContentManagerTypes::Local => {
                 let config = get_config();
-                let wallpaper_path = PathBuf::from(config.file_config.local.path.clone());
+                let wallpaper_path =
+                    PathBuf::from(config.file_config.local.path.clone()).join(self.id.clone());
                 let meta = fs::metadata(&wallpaper_path).map_err(|_| ())?;
                 if meta.len() == 0 {
                     tracing::error!("file has no bytes");
                     return Err(());
                 }
-                Ok(wallpaper_path.join(self.id.clone()))
+                Ok(wallpaper_path)
             }
             ContentManagerTypes::Git => GitContentManager::get_temp_file(&self.id),
         }
     }
 }
+
+#[cfg(test)]
+impl Wallpaper {
+    /// Creates a local wallpaper backed by a real file in the test wallpapers directory
+    pub fn new_test_file(id: &str) -> Wallpaper {
+        crate::CONFIG.get_or_init(crate::config::Config::create_config);
+
+        let dir = PathBuf::from(get_config().file_config.local.path.clone());
+        fs::create_dir_all(&dir).expect("failed to create test wallpapers directory");
+        fs::write(dir.join(id), id.as_bytes()).expect("failed to write test wallpaper");
+
+        Wallpaper::new(id.to_string(), ContentManagerTypes::Local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::error::Error;
+    use std::rc::Rc;
+
+    use chrono::{Local, TimeDelta, TimeZone};
+
+    use crate::CONFIG;
+    use crate::backends::recording::RecordingBackend;
+    use crate::clock::{Clock, FakeClock};
+    use crate::config::Config;
+    use crate::tags::TagSource;
+
+    use super::*;
+
+    struct TestContentManager {
+        wallpapers: Vec<Wallpaper>,
+        // non wallpaper files in the source, by path
+        files: HashMap<String, String>,
+        cleaned: RefCell<Vec<String>>,
+    }
+
+    impl TestContentManager {
+        fn new(ids: &[&str]) -> TestContentManager {
+            TestContentManager {
+                wallpapers: ids.iter().map(|id| Wallpaper::new_test_file(id)).collect(),
+                files: HashMap::new(),
+                cleaned: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl WallpaperContentManager for TestContentManager {
+        fn get_wallpapers(&self) -> Result<SourceScan, WallpaperContentManagerError> {
+            Ok(SourceScan::new(self.wallpapers.clone(), |file| {
+                self.files.get(file).cloned()
+            }))
+        }
+
+        fn cleanup_wallpaper(&self, wallpaper: Wallpaper) -> bool {
+            self.cleaned.borrow_mut().push(wallpaper.id);
+            true
+        }
+    }
+
+    fn setup(
+        ids: &[&str],
+    ) -> Result<(Store, RecordingBackend, TestContentManager), Box<dyn Error>> {
+        CONFIG.get_or_init(Config::create_config);
+        let content_manager = TestContentManager::new(ids);
+        let store = Store::new()?;
+        Ok((store, RecordingBackend::new(), content_manager))
+    }
+
+    fn current_id(manager: &WallpapersManager) -> Option<String> {
+        manager
+            .get_current_wallpaper()
+            .map(|wallpaper| wallpaper.id)
+    }
+
+    #[test]
+    fn test_set_next_wallpaper() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) = setup(&["manager-next.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+        assert!(store.get_last_update().is_none());
+
+        manager.set_next_wallpaper(&content_manager)?;
+
+        let wallpaper = &content_manager.wallpapers[0];
+        let path = wallpaper.get_wallpaper_path().unwrap();
+        assert_eq!(backend.calls(), vec![path.to_string_lossy().to_string()]);
+        assert_eq!(backend.current(), Some(path.to_string_lossy().to_string()));
+        assert_eq!(current_id(&manager).as_deref(), Some("manager-next.png"));
+        assert!(store.have_seen(wallpaper));
+        assert!(store.get_last_update().is_some());
+        assert!(store.get_last_error().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_next_wallpaper_cleans_up_previous() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) =
+            setup(&["manager-cleanup-1.png", "manager-cleanup-2.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend);
+        manager.store_wallpapers(&content_manager)?;
+
+        manager.set_next_wallpaper(&content_manager)?;
+        let first = current_id(&manager).unwrap();
+        assert!(content_manager.cleaned.borrow().is_empty());
+
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(*content_manager.cleaned.borrow(), vec![first]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seen_state_resets_after_full_cycle() -> Result<(), Box<dyn Error>> {
+        let ids = [
+            "manager-cycle-1.png",
+            "manager-cycle-2.png",
+            "manager-cycle-3.png",
+        ];
+        let (store, backend, content_manager) = setup(&ids)?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+
+        let mut shown = HashSet::new();
+        for _ in 0..ids.len() {
+            manager.set_next_wallpaper(&content_manager)?;
+            shown.insert(current_id(&manager).unwrap());
+        }
+        assert_eq!(
+            shown.len(),
+            ids.len(),
+            "a wallpaper repeated within one cycle"
+        );
+        assert!(store.get_unseen_wallpaperrs().is_empty());
+
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(backend.calls().len(), ids.len() + 1);
+        assert_eq!(store.get_unseen_wallpaperrs().len(), ids.len() - 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_banned_wallpaper_is_never_picked() -> Result<(), Box<dyn Error>> {
+        let (store, _, content_manager) = setup(&["manager-banned.png", "manager-allowed.png"])?;
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+        store.set_banned("manager-banned.png", true)?;
+
+        // several full cycles, each one resetting the seen state
+        for _ in 0..5 {
+            manager.set_next_wallpaper(&content_manager)?;
+            assert_eq!(current_id(&manager).as_deref(), Some("manager-allowed.png"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_weight_wallpaper_is_never_picked() -> Result<(), Box<dyn Error>> {
+        let (store, _, content_manager) = setup(&[
+            "manager-weightless.png",
+            "manager-weighted-1.png",
+            "manager-weighted-2.png",
+        ])?;
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+        store.set_weight("manager-weightless.png", 0.0)?;
+
+        // a cycle ends once only the zero weight is left unseen
+        for _ in 0..6 {
+            manager.set_next_wallpaper(&content_manager)?;
+            assert_ne!(
+                current_id(&manager).as_deref(),
+                Some("manager-weightless.png")
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_removed_wallpaper_is_never_picked() -> Result<(), Box<dyn Error>> {
+        let christmas = Local
+            .with_ymd_and_hms(2024, 12, 25, 12, 0, 0)
+            .earliest()
+            .unwrap();
+        let clock = Rc::new(FakeClock::new(christmas));
+        let store = Store::with_clock(clock.clone())?;
+        let mut content_manager = TestContentManager::new(&[
+            "manager-removed.png",
+            "manager-remaining-1.png",
+            "manager-remaining-2.png",
+        ]);
+        content_manager.files.insert(
+            CALENDAR_FILE.to_string(),
+            "[[overrides]]\ndate = \"12-25\"\nwallpaper = \"manager-removed.png\"\n".to_string(),
+        );
+        content_manager.files.insert(
+            PLAYLIST_FILE.to_string(),
+            "[mixed]\nwallpapers = [\"manager-removed.png\", \"manager-remaining-1.png\"]\n"
+                .to_string(),
+        );
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+
+        content_manager.wallpapers.remove(0);
+        manager.store_wallpapers(&content_manager)?;
+
+        // the override only names the removed wallpaper, so it is ignored
+        for _ in 0..4 {
+            manager.set_next_wallpaper(&content_manager)?;
+            assert_ne!(current_id(&manager).as_deref(), Some("manager-removed.png"));
+        }
+
+        clock.advance(TimeDelta::days(2));
+        store.set_active_playlist(Some("mixed"));
+        for _ in 0..3 {
+            manager.set_next_wallpaper(&content_manager)?;
+            assert_eq!(
+                current_id(&manager).as_deref(),
+                Some("manager-remaining-1.png")
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_back_to_back_repeat_across_cycles() -> Result<(), Box<dyn Error>> {
+        let (store, _, content_manager) = setup(&["manager-repeat-1.png", "manager-repeat-2.png"])?;
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+
+        // every other change starts a new cycle, where the last one would be eligible again
+        let mut previous = None;
+        for _ in 0..10 {
+            manager.set_next_wallpaper(&content_manager)?;
+            let current = current_id(&manager);
+            assert_ne!(current, previous);
+            previous = current;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seeded_pick_is_shared_between_instances() -> Result<(), Box<dyn Error>> {
+        let ids = [
+            "manager-seeded-1.png",
+            "manager-seeded-2.png",
+            "manager-seeded-3.png",
+            "manager-seeded-4.png",
+        ];
+        let mut reversed = ids;
+        reversed.reverse();
+        let midday = Local
+            .with_ymd_and_hms(2024, 6, 1, 12, 0, 0)
+            .earliest()
+            .unwrap();
+
+        CONFIG.get_or_init(Config::create_config);
+
+        // two machines with their own state, scanning the files in a different order
+        let first_store = Store::with_clock(Rc::new(FakeClock::new(midday)))?;
+        let first = WallpapersManager::new(&first_store, RecordingBackend::new());
+        let first_source = TestContentManager::new(&ids);
+        first.store_wallpapers(&first_source)?;
+        first_store.add_history(&first_source.wallpapers[0]);
+
+        let second_store = Store::with_clock(Rc::new(FakeClock::new(midday)))?;
+        let second = WallpapersManager::new(&second_store, RecordingBackend::new());
+        let mut second_source = TestContentManager::new(&reversed);
+        // a wallpaper removed from the source since an earlier scan
+        second_source
+            .wallpapers
+            .push(Wallpaper::new_test_file("manager-seeded-5.png"));
+        second.store_wallpapers(&second_source)?;
+        second_source.wallpapers.pop();
+        second.store_wallpapers(&second_source)?;
+
+        let picked = first.pick_seeded_wallpaper(0).unwrap().id;
+        assert_eq!(second.pick_seeded_wallpaper(0).unwrap().id, picked);
+
+        // a local ban only moves that machine along to the next wallpaper
+        second_store.set_banned(&picked, true)?;
+        assert_eq!(
+            second.pick_seeded_wallpaper(0).unwrap().id,
+            first.pick_seeded_wallpaper(1).unwrap().id
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tag_filter() -> Result<(), Box<dyn Error>> {
+        let (store, _, mut content_manager) = setup(&[
+            "manager-tag-forest.png",
+            "manager-tag-lake.png",
+            "manager-tag-city.png",
+        ])?;
+        content_manager.wallpapers[0].tags = vec![Tag::new("nature", TagSource::Sidecar)];
+        content_manager.wallpapers[1].tags = vec![
+            Tag::new("nature", TagSource::Directory),
+            Tag::new("blue", TagSource::Index),
+        ];
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+        manager.filter = TagFilter {
+            include: vec!["nature".to_string()],
+            exclude: vec!["blue".to_string()],
+        };
+
+        // the filter is applied across seen state resets too
+        for _ in 0..4 {
+            manager.set_next_wallpaper(&content_manager)?;
+            assert_eq!(
+                current_id(&manager).as_deref(),
+                Some("manager-tag-forest.png")
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_wallpaper_is_skipped() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) = setup(&["manager-readable.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+        let missing = Wallpaper::new(
+            "manager-missing.png".to_string(),
+            ContentManagerTypes::Local,
+        );
+        store.insert_wallpaper(&missing)?;
+        store.mark_as_seen(&content_manager.wallpapers[0])?;
+
+        // only the missing wallpaper is unseen, so it has to be tried and skipped first
+        manager.set_next_wallpaper(&content_manager)?;
+
+        assert_eq!(
+            current_id(&manager).as_deref(),
+            Some("manager-readable.png")
+        );
+        assert_eq!(backend.calls().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retryable_failures_are_retried() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) = setup(&["manager-retry.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+
+        backend.fail_next(WallpaperBackendError::Rejected("daemon busy".to_string()));
+        backend.fail_next(WallpaperBackendError::NotReady);
+        manager.set_next_wallpaper(&content_manager)?;
+
+        assert_eq!(backend.calls().len(), 3);
+        assert_eq!(current_id(&manager).as_deref(), Some("manager-retry.png"));
+        assert!(store.get_last_error().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_persistent_failure_is_reported() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) = setup(&["manager-failing.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+
+        let attempts = get_config().file_config.retry.attempts;
+        for _ in 0..attempts {
+            backend.fail_next(WallpaperBackendError::Rejected("daemon busy".to_string()));
+        }
+        let result = manager.set_next_wallpaper(&content_manager);
+
+        assert!(matches!(result, Err(WallpaperBackendError::Rejected(_))));
+        assert_eq!(backend.calls().len(), attempts as usize);
+        assert!(backend.current().is_none());
+        assert!(current_id(&manager).is_none());
+        assert!(store.get_last_update().is_none());
+        assert!(store.get_last_error().unwrap().contains("daemon busy"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_retryable_failure_is_not_retried() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) = setup(&["manager-missing-bin.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+
+        backend.fail_next(WallpaperBackendError::BinaryMissing("swww".to_string()));
+        let result = manager.set_next_wallpaper(&content_manager);
+
+        assert!(matches!(
+            result,
+            Err(WallpaperBackendError::BinaryMissing(_))
+        ));
+        assert_eq!(backend.calls().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_update_follows_clock() -> Result<(), Box<dyn Error>> {
+        let content_manager = TestContentManager::new(&["manager-clock.png"]);
+        let midday = Local
+            .with_ymd_and_hms(2024, 6, 1, 12, 0, 0)
+            .earliest()
+            .unwrap();
+        let clock = Rc::new(FakeClock::new(midday));
+        let store = Store::with_clock(clock.clone())?;
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+
+        assert!(manager.should_update_wallpaper());
+        manager.set_next_wallpaper(&content_manager)?;
+        assert!(!manager.should_update_wallpaper());
+
+        // the default interval changes the wallpaper daily at midnight
+        clock.advance(TimeDelta::hours(11) + TimeDelta::minutes(59));
+        assert!(!manager.should_update_wallpaper());
+        clock.set(
+            Local
+                .with_ymd_and_hms(2024, 6, 2, 0, 0, 0)
+                .earliest()
+                .unwrap(),
+        );
+        assert!(manager.should_update_wallpaper());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resync_after_clock_set_back() -> Result<(), Box<dyn Error>> {
+        let content_manager = TestContentManager::new(&["manager-resync.png"]);
+        let evening = Local
+            .with_ymd_and_hms(2024, 6, 1, 20, 0, 0)
+            .earliest()
+            .unwrap();
+        let clock = Rc::new(FakeClock::new(evening));
+        let store = Store::with_clock(clock.clone())?;
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+        manager.set_next_wallpaper(&content_manager)?;
+
+        // a clock that was a day ahead gets corrected
+        clock.advance(-TimeDelta::days(1));
+        assert!(manager.should_update_wallpaper());
+        manager.resync_schedule();
+        assert!(!manager.should_update_wallpaper());
+        assert_eq!(store.get_last_update(), Some(clock.now()));
+
+        // a last run in the past is left alone
+        clock.advance(TimeDelta::hours(1));
+        manager.resync_schedule();
+        assert_eq!(
+            store.get_last_update(),
+            Some(clock.now() - TimeDelta::hours(1))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pause_runs_out() -> Result<(), Box<dyn Error>> {
+        CONFIG.get_or_init(Config::create_config);
+        let clock = Rc::new(FakeClock::new(Local::now()));
+        let store = Store::with_clock(clock.clone())?;
+        let manager = WallpapersManager::new(&store, RecordingBackend::new());
+        assert!(!manager.is_paused());
+
+        store.set_pause(Some(Pause::Indefinite));
+        clock.advance(TimeDelta::days(30));
+        assert!(manager.is_paused());
+
+        store.set_pause(Some(Pause::Until(clock.now() + TimeDelta::hours(1))));
+        clock.advance(TimeDelta::minutes(59));
+        assert!(manager.is_paused());
+        clock.advance(TimeDelta::minutes(1));
+        assert!(!manager.is_paused());
+        assert!(store.get_pause().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backend_readiness() -> Result<(), Box<dyn Error>> {
+        let (store, backend, _) = setup(&[])?;
+        let manager = WallpapersManager::new(&store, backend.clone());
+
+        assert!(manager.is_backend_ready());
+        backend.set_ready(false);
+        assert!(!manager.is_backend_ready());
+
+        Ok(())
+    }
+
+    fn loop_setup(
+        ids: &[&str],
+    ) -> Result<(Store, RecordingBackend, TestContentManager), Box<dyn Error>> {
+        let (_, backend, content_manager) = setup(ids)?;
+        // a fixed clock so the wallpaper never falls due during the test
+        let midday = Local
+            .with_ymd_and_hms(2024, 6, 1, 12, 0, 0)
+            .earliest()
+            .unwrap();
+        let store = Store::with_clock(Rc::new(FakeClock::new(midday)))?;
+        Ok((store, backend, content_manager))
+    }
+
+    #[test]
+    fn test_check_reapplies_when_backend_returns() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) = loop_setup(&["loop-ready.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+        manager.set_next_wallpaper(&content_manager)?;
+        let mut state = LoopState::new(false);
+
+        manager.check(&mut state, &content_manager);
+        assert_eq!(backend.calls().len(), 1);
+
+        backend.set_ready(false);
+        manager.check(&mut state, &content_manager);
+        assert_eq!(backend.calls().len(), 1);
+        assert!(store.get_last_error().is_some());
+
+        backend.set_ready(true);
+        manager.check(&mut state, &content_manager);
+        let calls = backend.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0], calls[1]);
+        assert!(!state.needs_restore);
+        assert!(store.get_last_error().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_picks_new_wallpaper_when_restore_fails() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) =
+            loop_setup(&["loop-restore-a.png", "loop-restore-b.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+        manager.set_next_wallpaper(&content_manager)?;
+        let previous = current_id(&manager);
+        let mut state = LoopState::new(true);
+
+        backend.fail_next(WallpaperBackendError::UnsupportedOption(
+            "loop test".to_string(),
+        ));
+        manager.check(&mut state, &content_manager);
+
+        assert_ne!(current_id(&manager), previous);
+        assert!(current_id(&manager).is_some());
+        assert!(!state.needs_restore);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_last_wallpaper() -> Result<(), Box<dyn Error>> {
+        let (store, backend, content_manager) = setup(&["manager-last.png"])?;
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+
+        // nothing has been shown yet, so there is nothing to restore
+        manager.set_last_wallpaper()?;
+        assert!(backend.calls().is_empty());
+
+        manager.set_next_wallpaper(&content_manager)?;
+        manager.set_last_wallpaper()?;
+
+        let calls = backend.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0], calls[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_switching_playlists_keeps_progress() -> Result<(), Box<dyn Error>> {
+        let (store, _, mut content_manager) = setup(&[
+            "manager-playlist-1.png",
+            "manager-playlist-2.png",
+            "manager-playlist-3.png",
+            "manager-playlist-4.png",
+        ])?;
+        content_manager.files.insert(
+            PLAYLIST_FILE.to_string(),
+            r#"
+            [first]
+            wallpapers = ["manager-playlist-3.png", "manager-playlist-1.png", "manager-playlist-unknown.png"]
+
+            [second]
+            wallpapers = ["manager-playlist-4.png", "manager-playlist-2.png"]
+            "#
+            .to_string(),
+        );
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+
+        store.set_active_playlist(Some("first"));
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(
+            current_id(&manager).as_deref(),
+            Some("manager-playlist-3.png")
+        );
+
+        store.set_active_playlist(Some("second"));
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(
+            current_id(&manager).as_deref(),
+            Some("manager-playlist-4.png")
+        );
+
+        // back to the first playlist, carrying on where it was left
+        store.set_active_playlist(Some("first"));
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(
+            current_id(&manager).as_deref(),
+            Some("manager-playlist-1.png")
+        );
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(
+            current_id(&manager).as_deref(),
+            Some("manager-playlist-3.png")
+        );
+
+        // the whole collection has its own seen state, untouched by the playlists
+        assert_eq!(store.get_unseen_wallpaperrs().len(), 4);
+
+        store.set_active_playlist(Some("removed"));
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(store.get_unseen_wallpaperrs().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_date_override_takes_precedence() -> Result<(), Box<dyn Error>> {
+        let christmas = Local
+            .with_ymd_and_hms(2024, 12, 25, 12, 0, 0)
+            .earliest()
+            .unwrap();
+        let clock = Rc::new(FakeClock::new(christmas));
+        let store = Store::with_clock(clock.clone())?;
+        let mut content_manager = TestContentManager::new(&[
+            "manager-override-tree.png",
+            "manager-override-snow.png",
+            "manager-override-beach.png",
+        ]);
+        content_manager.wallpapers[0].tags = vec![Tag::new("christmas", TagSource::Directory)];
+        content_manager.files.insert(
+            CALENDAR_FILE.to_string(),
+            "[[overrides]]\nstart = \"12-24\"\nend = \"12-26\"\ntag = \"Christmas\"\n".to_string(),
+        );
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+
+        for _ in 0..3 {
+            manager.set_next_wallpaper(&content_manager)?;
+            assert_eq!(
+                current_id(&manager).as_deref(),
+                Some("manager-override-tree.png")
+            );
+        }
+        // shown outside the rotation, which still has everything left
+        assert_eq!(store.get_unseen_wallpaperrs().len(), 3);
+
+        // back to the normal rotation once the range is over
+        clock.advance(TimeDelta::days(2));
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(store.get_unseen_wallpaperrs().len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seasonal_collection_combines_with_tag_filter() -> Result<(), Box<dyn Error>> {
+        let october = Local
+            .with_ymd_and_hms(2024, 10, 15, 12, 0, 0)
+            .earliest()
+            .unwrap();
+        let clock = Rc::new(FakeClock::new(october));
+        let store = Store::with_clock(clock.clone())?;
+        let mut content_manager = TestContentManager::new(&[
+            "manager-season-maple.png",
+            "manager-season-storm.png",
+            "manager-season-beach.png",
+        ]);
+        content_manager.wallpapers[0].tags = vec![Tag::new("autumn", TagSource::Directory)];
+        content_manager.wallpapers[1].tags = vec![
+            Tag::new("autumn", TagSource::Directory),
+            Tag::new("dark", TagSource::Sidecar),
+        ];
+        let mut manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+        manager.filter = TagFilter {
+            include: vec![],
+            exclude: vec!["dark".to_string()],
+        };
+        manager.seasonal = toml::from_str("[seasons]\nautumn = [\"autumn\"]")?;
+
+        for _ in 0..3 {
+            manager.set_next_wallpaper(&content_manager)?;
+            assert_eq!(
+                current_id(&manager).as_deref(),
+                Some("manager-season-maple.png")
+            );
+        }
+
+        // nothing is mapped to winter, so only the tag filter applies
+        clock.set(october + TimeDelta::days(60));
+        let mut shown = HashSet::new();
+        for _ in 0..4 {
+            manager.set_next_wallpaper(&content_manager)?;
+            shown.insert(current_id(&manager).unwrap());
+        }
+        assert_eq!(
+            shown,
+            HashSet::from([
+                "manager-season-maple.png".to_string(),
+                "manager-season-beach.png".to_string()
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_override_wallpaper_is_not_picked_again() -> Result<(), Box<dyn Error>> {
+        let christmas = Local
+            .with_ymd_and_hms(2024, 12, 25, 12, 0, 0)
+            .earliest()
+            .unwrap();
+        let store = Store::with_clock(Rc::new(FakeClock::new(christmas)))?;
+        let mut content_manager = TestContentManager::new(&["manager-override-readable.png"]);
+        let mut missing = Wallpaper::new(
+            "manager-override-missing.png".to_string(),
+            ContentManagerTypes::Local,
+        );
+        for wallpaper in [&mut content_manager.wallpapers[0], &mut missing] {
+            wallpaper.tags = vec![Tag::new("christmas", TagSource::Directory)];
+        }
+        content_manager.files.insert(
+            CALENDAR_FILE.to_string(),
+            "[[overrides]]\ndate = \"12-25\"\ntag = \"christmas\"\n".to_string(),
+        );
+        let backend = RecordingBackend::new();
+        let mut manager = WallpapersManager::new(&store, backend.clone());
+        manager.store_wallpapers(&content_manager)?;
+        store.insert_wallpaper(&missing)?;
+        store.set_scanned_tags(&missing.id, &missing.tags)?;
+
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(
+            current_id(&manager).as_deref(),
+            Some("manager-override-readable.png")
+        );
+
+        // only the unreadable wallpaper is left, tried once rather than until giving up
+        store.set_banned("manager-override-readable.png", true)?;
+        manager.set_next_wallpaper(&content_manager)?;
+        assert_eq!(backend.calls().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_renames_and_duplicates_by_content() -> Result<(), Box<dyn Error>> {
+        let (store, _, mut content_manager) = setup(&[
+            "manager-hash-a.png",
+            "manager-hash-b.png",
+            "manager-hash-c.png",
+        ])?;
+        for (wallpaper, hash) in content_manager.wallpapers.iter_mut().zip(["1", "2", "1"]) {
+            wallpaper.hash = Some(hash.to_string());
+        }
+        content_manager.wallpapers[2].tags = vec![Tag::new("copy", TagSource::Sidecar)];
+        let manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+
+        // c has the same content as a, so only a is stored, with c's tags
+        assert!(store.get_wallpaper("manager-hash-c.png").is_none());
+        assert_eq!(store.get_inserted_wallpapers().len(), 2);
+        assert_eq!(
+            store.get_tags("manager-hash-a.png"),
+            [Tag::new("copy", TagSource::Sidecar)]
+        );
+
+        store.set_banned("manager-hash-a.png", true)?;
+        store.mark_as_seen(&content_manager.wallpapers[1])?;
+
+        // both are renamed, c now being the only copy of a's content
+        content_manager.wallpapers.remove(0);
+        content_manager.wallpapers[0].id = "manager-hash-renamed.png".to_string();
+        manager.store_wallpapers(&content_manager)?;
+
+        assert!(store.get_wallpaper("manager-hash-a.png").is_none());
+        assert!(store.get_wallpaper("manager-hash-c.png").unwrap().banned);
+        assert!(store.get_wallpaper("manager-hash-b.png").is_none());
+        assert!(
+            store
+                .get_wallpaper("manager-hash-renamed.png")
+                .unwrap()
+                .seen
+        );
+        assert_eq!(store.get_inserted_wallpapers().len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_hash_is_reused_while_unmodified() -> Result<(), Box<dyn Error>> {
+        let (store, _, mut content_manager) = setup(&["manager-hash-cached.png"])?;
+        content_manager.wallpapers[0].modified = Some(1_700_000_000);
+        let manager = WallpapersManager::new(&store, RecordingBackend::new());
+        manager.store_wallpapers(&content_manager)?;
+
+        let stored_hash =
+            |store: &Store| store.get_wallpaper("manager-hash-cached.png").unwrap().hash;
+        let original = stored_hash(&store);
+        assert_eq!(
+            original,
+            Some(hash::blob_id(&b"manager-hash-cached.png"[..], 23)?)
+        );
+
+        // not read again while the modified time is the same
+        let path = content_manager.wallpapers[0].get_wallpaper_path().unwrap();
+        fs::write(&path, "changed")?;
+        manager.store_wallpapers(&content_manager)?;
+        assert_eq!(stored_hash(&store), original);
+
+        content_manager.wallpapers[0].modified = Some(1_700_000_001);
+        manager.store_wallpapers(&content_manager)?;
+        assert_eq!(
+            stored_hash(&store),
+            Some(hash::blob_id(&b"changed"[..], 7)?)
+        );
+
+        fs::write(&path, "manager-hash-cached.png")?;
+        Ok(())
+    }
+}